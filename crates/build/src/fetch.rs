@@ -0,0 +1,45 @@
+use crate::solve::{self, Selection};
+use crate::Result;
+use mix_atom::Requirement;
+use mix_packages::Packages;
+use mix_triple::Triple;
+use path::PathBuf;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub target: Triple,
+    pub requirements: Vec<Requirement>,
+    pub all: bool,
+}
+
+/// Download the sources of packages and their dependencies, without building anything.
+pub async fn fetch(
+    config: mix_config::Config,
+    fetch_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let packages = packages.for_target(fetch_config.target);
+
+    if fetch_config.all {
+        let dependencies = packages.iter().cloned().collect::<Vec<_>>();
+        let sources = crate::unique_sources(&dependencies);
+
+        return crate::fetch_sources(&config, &sources).await;
+    }
+
+    let steps = solve::solve(&config, &packages, &fetch_config.requirements, &[]).await?;
+
+    for step in steps {
+        if let Selection::Build(entry) = &step.selection {
+            let checksums = step.package.checksums_for(entry);
+
+            config
+                .download_file_checked(&entry.path, &entry.url, &checksums)
+                .await?;
+        }
+    }
+
+    Ok(())
+}
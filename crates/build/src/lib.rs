@@ -8,15 +8,22 @@
 
 use self::process::Command;
 use crate::compiler::{Compiler, Linker};
+use crate::lock::Lock;
+use crate::options::Options;
+use crate::record::Record;
+use crate::solve::{Selection, Step};
 use command_extra::{Line, Lines, Stdio};
 use futures_util::stream::TryStreamExt;
 use mix_atom::Requirement;
+use mix_id::{PackageId, RepositoryId};
 use mix_packages::{Package, Packages};
 use mix_shell::{header, write, AsyncWrite};
+use mix_source::Source;
 use mix_triple::{Arch, Triple};
-use path::PathBuf;
-use std::borrow::Borrow;
+use mix_version::Version;
+use path::{Path, PathBuf};
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tokio::time;
 use tokio::time::Duration;
@@ -27,13 +34,19 @@ pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
 //mod autotools;
 mod compiler;
 mod configs;
+mod options;
+mod patch;
 mod process;
+mod recipe;
 
-#[derive(Debug)]
-pub enum Value {
-    Bool(bool),
-    String(String),
-}
+pub mod fetch;
+pub mod lock;
+pub mod record;
+pub mod solve;
+pub mod tree;
+pub mod upgrade;
+
+pub use mix_manifest::Value;
 
 #[derive(Debug)]
 pub struct Config {
@@ -41,9 +54,13 @@ pub struct Config {
     pub target: Triple,
     pub requirement: Requirement,
     pub jobs: usize,
+    /// Build options of the requested package, or `--enable` flags where it doesn't declare them.
     pub define: Vec<(String, Value)>,
+    /// Ditto, or `--with` flags.
     pub include: Vec<(String, Value)>,
     pub build_dir: bool,
+    pub lock: Option<PathBuf>,
+    pub locked: Option<PathBuf>,
 }
 
 pub(crate) const CLANG: &str = "clang";
@@ -53,54 +70,237 @@ pub(crate) const GXX: &str = "g++";
 pub(crate) const EN_US: &str = "en_US.UTF-8";
 pub(crate) const LD: &str = "ld";
 pub(crate) const LLD: &str = "ld.lld";
+pub(crate) const LIBC: &str = "libc";
+pub(crate) const CC: &str = "cc";
+pub(crate) const LLVM: &str = "llvm";
 
 use std::collections::HashSet;
 
-pub fn resolve(
+/// The package matching `requirement`, preferring repositories by priority.
+///
+/// Returns `None` when nothing matches, or the highest priority is shared.
+pub(crate) fn resolve_one<'a>(
     config: &mix_config::Config,
-    packages: &Arc<Packages>,
-    dependencies: &mut Vec<Package>,
-    exists: &mut HashSet<Package>,
-    requirement: &Requirement,
-) {
+    packages: &'a Packages,
+    requirement: &'a Requirement,
+) -> Option<&'a Package> {
     let matches = packages.matches(requirement).collect::<Vec<_>>();
 
-    if matches.len() != 1 {
-        return;
+    prefer(config, &matches).map(|(package, _shadowed)| package)
+}
+
+/// Pick one of `matches` by repository priority, alongside the packages it shadows.
+///
+/// Installed packages win between repositories of the same priority, which picks the installed
+/// provider of a virtual package. Returns `None` when `matches` is empty, or the highest
+/// priority is still shared.
+pub(crate) fn prefer<'a>(
+    config: &mix_config::Config,
+    matches: &[&'a Package],
+) -> Option<(&'a Package, Vec<&'a Package>)> {
+    let priority = |package: &Package| {
+        (
+            config.priority(package.repository_id()),
+            !package.installed(),
+        )
+    };
+
+    let highest = matches.iter().map(|package| priority(package)).min()?;
+    let mut preferred = matches
+        .iter()
+        .filter(|package| priority(package) == highest);
+
+    let package = *preferred.next()?;
+
+    if preferred.next().is_some() {
+        return None;
+    }
+
+    let shadowed = matches
+        .iter()
+        .copied()
+        .filter(|shadowed| *shadowed != package)
+        .collect();
+
+    Some((package, shadowed))
+}
+
+/// Virtual packages everything is built against, and the install under `core` used when
+/// nothing provides them.
+const TOOLCHAIN: [(&str, &str, &str); 2] = [(LIBC, "glibc", "2.34.0"), (CC, GCC, "11.2.0")];
+
+/// The install of a virtual package, such as `libc`, which builds use.
+#[derive(Debug)]
+pub(crate) struct Provider {
+    pub package_id: PackageId,
+    pub version: Version,
+    pub root: PathBuf,
+}
+
+/// Where whatever provides the virtual package `id`, such as `libc`, is installed.
+///
+/// A provider in `steps` wins, as it's installed by the time anything depending on it is built,
+/// then the newest install of the preferred installed provider, then the `core` install in
+/// `TOOLCHAIN`.
+pub(crate) fn provider_install(
+    config: &mix_config::Config,
+    build_config: &Config,
+    packages: &Packages,
+    steps: &[Step],
+    id: &str,
+) -> Result<Provider> {
+    let package_id = PackageId::try_from(id)?;
+    let selected = steps
+        .iter()
+        .find(|step| step.package.provides().contains(&package_id));
+
+    if let Some(Step { package, selection }) = selected {
+        let version = selection.version().clone();
+        let path = match selection {
+            Selection::Installed(version) => package
+                .versions()
+                .pairs()
+                .find(|(installed, _path)| *installed == version)
+                .map(|(_version, path)| path.to_path_buf()),
+            Selection::Build(_entry) => None,
+        };
+
+        let root = path.unwrap_or_else(|| {
+            build_config
+                .prefix
+                .join(build_config.target.as_str())
+                .join(package.repository_id())
+                .join(package.package_id())
+                .join(version.to_string())
+        });
+
+        return Ok(Provider {
+            package_id: package.package_id().clone(),
+            version,
+            root,
+        });
+    }
+
+    let installed = packages
+        .providers_iter(&package_id)
+        .filter(|package| package.installed())
+        .collect::<Vec<_>>();
+
+    if let Some((package, _shadowed)) = prefer(config, &installed) {
+        let (version, path) = package
+            .versions()
+            .pairs()
+            .last()
+            .expect("installed package has a version");
+
+        return Ok(Provider {
+            package_id: package.package_id().clone(),
+            version: version.clone(),
+            root: path.to_path_buf(),
+        });
+    }
+
+    let (_provides, fallback_id, version) = TOOLCHAIN
+        .into_iter()
+        .find(|(provides, _package_id, _version)| *provides == id)
+        .ok_or_else(|| format!("nothing provides `{}`", id))?;
+
+    let root = build_config
+        .prefix
+        .join(build_config.target.as_str())
+        .join(RepositoryId::CORE.as_str())
+        .join(fallback_id)
+        .join(version);
+
+    Ok(Provider {
+        package_id: PackageId::try_from(fallback_id)?,
+        version: Version::parse(version)?,
+        root,
+    })
+}
+
+/// The runtime library of the compiler `provider` installs, as its directory and the name to
+/// link it by.
+///
+/// gcc keeps `libgcc` in `lib/gcc/<triple>/<version>`, clang keeps compiler-rt builtins in
+/// `lib/clang/<version>/lib/<triple>`, or `lib/clang/<version>/lib/linux` named after the
+/// architecture. The triple is whatever the compiler was configured for, which need not be
+/// spelled like `target`, so it's looked up, and only guessed before the compiler is installed.
+pub(crate) async fn compiler_runtime(
+    provider: &Provider,
+    target: Triple,
+) -> Result<(PathBuf, String)> {
+    let lib = provider.root.join("lib");
+    let gcc = lib.join(GCC);
+    let clang = lib.join(CLANG);
+    let version = provider.version.to_string();
+
+    let is_clang = if clang.exists_async().await {
+        true
+    } else if gcc.exists_async().await {
+        false
+    } else {
+        matches!(provider.package_id.as_str(), CLANG | LLVM)
+    };
+
+    if !is_clang {
+        let dir = match subdir_with(&gcc, &version).await? {
+            Some(dir) => dir.join(&version),
+            None => gcc.join(target.as_str()).join(&version),
+        };
+
+        return Ok((dir, GCC.to_string()));
     }
 
-    // SAFETY: above clause requires at least 1 package to be present.
-    let package = unsafe { matches.get_unchecked(0) };
+    // newer releases name the directory after the major version only
+    let major = provider.version.major().to_string();
+    let mut version_dir = clang.join(&major);
+
+    for name in [&version, &major] {
+        if clang.join(name).is_dir_async().await {
+            version_dir = clang.join(name);
 
-    if exists.contains(Borrow::<Package>::borrow(package)) {
-        return;
+            break;
+        }
     }
 
-    dependencies.push(Package::clone(package));
-    exists.insert(Package::clone(package));
+    let linux = version_dir.join("lib").join("linux");
+
+    if linux.is_dir_async().await {
+        let (arch, _sys, _env) = target.as_tuple();
 
-    for requirement in package.dependencies() {
-        resolve(config, packages, dependencies, exists, requirement);
+        return Ok((linux, format!("clang_rt.builtins-{}", arch)));
     }
+
+    let dir = match subdir_with(&version_dir.join("lib"), "libclang_rt.builtins.a").await? {
+        Some(dir) => dir,
+        None => version_dir.join("lib").join(target.as_str()),
+    };
+
+    Ok((dir, "clang_rt.builtins".to_string()))
 }
 
-/// Build a specific package.
-pub async fn build(
-    config: mix_config::Config,
-    build_config: Config,
-    packages: Arc<Packages>,
-) -> Result<()> {
-    let mut dependencies = Vec::new();
-    let mut exists = HashSet::new();
+/// The first entry of `dir` which has `name` in it.
+async fn subdir_with(dir: &Path, name: &str) -> Result<Option<PathBuf>> {
+    if !dir.is_dir_async().await {
+        return Ok(None);
+    }
 
-    resolve(
-        &config,
-        &packages,
-        &mut dependencies,
-        &mut exists,
-        &build_config.requirement,
-    );
+    let mut entries = dir.read_dir_async().await?;
+
+    while let Some(entry) = entries.try_next().await? {
+        let path = entry.path();
 
+        if path.join(name).exists_async().await {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Sources of `dependencies` in build order, without duplicates.
+pub(crate) fn unique_sources(dependencies: &[Package]) -> Vec<(Package, &Source)> {
     let mut sources = Vec::new();
     let mut exists = HashSet::new();
 
@@ -115,521 +315,450 @@ pub async fn build(
         if !exists.contains(source) {
             sources.push((package, source));
             exists.insert(source);
-        } else {
-            //println!("duplicate {:?}", package.name);
         }
     }
 
-    // fetch everything first
+    sources
+}
+
+/// Update the tags of each source and download the latest tarball into the cache.
+pub(crate) async fn fetch_sources(
+    config: &mix_config::Config,
+    sources: &[(Package, &Source)],
+) -> Result<()> {
     for (package, source) in sources.iter() {
-        source.update(&config).await?;
+        source.update(config).await?;
 
-        let versions = source.versions(&config).await?;
+        let versions = source.versions(config).await?;
 
         if let Some(entry) = versions.latest() {
-            //println!("{}/{}", package.repository_id(), package.package_id());
-
-            config.download_file(&entry.path, &entry.url).await?;
+            config
+                .download_file_checked(&entry.path, &entry.url, &package.checksums_for(entry))
+                .await?;
         }
     }
 
-    for (package, source) in sources {
-        let versions = source.versions(&config).await?;
-
-        if let Some(entry) = versions.latest() {
-            if package.versions().contains(&entry.version) {
-                continue;
-            }
-
-            println!("{}/{}", package.repository_id(), package.package_id());
-
-            let version_str = entry.version.to_string();
-            let build_dir = package.build_prefix().join(version_str);
-
-            //println!("{:?}", &build_dir);
-
-            let _ = build_dir.create_dir_all_async().await;
-            let mut command = Command::bsdtar();
-
-            command
-                .arg("xvf")
-                .arg(&entry.path)
-                .current_dir(&build_dir)
-                .home_dir("/")
-                .stderr(Stdio::piped())
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped());
+    Ok(())
+}
 
-            let mut child = command.spawn().await?;
-            let stdio = child.stdio()?.expect("stdio");
-            let mut lines = stdio.lines();
+/// Build a specific package.
+pub async fn build(
+    config: mix_config::Config,
+    build_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let packages = packages.for_target(build_config.target);
+    let requirements = vec![build_config.requirement.clone()];
+
+    let locked = match &build_config.locked {
+        Some(path) => Some(Lock::load(path).await?),
+        None => None,
+    };
+
+    let pins = match &locked {
+        Some(locked) => locked.pins()?,
+        None => Vec::new(),
+    };
+
+    let steps = solve::solve(&config, &packages, &requirements, &pins).await?;
+    let option_values = step_options(&config, &build_config, &packages, &steps).await?;
+
+    if let Some(locked) = &locked {
+        locked
+            .verify(
+                &config,
+                &Lock::from_steps(&config, &steps, &option_values).await?,
+            )
+            .await?;
+    }
 
-            tokio::spawn(async move {
-                // TODO: Proper error handling!
-                let _ = child.wait().await;
-            });
+    if let Some(path) = &build_config.lock {
+        Lock::from_steps(&config, &steps, &option_values)
+            .await?
+            .save(path)
+            .await?;
+
+        header!(
+            config.shell(),
+            "{} {}",
+            config.shell().theme().command_paint("locked"),
+            path
+        )?;
+    }
 
-            write!(config.shell(), "\r\x1b[K > extract")?;
-            config.shell().flush().await?;
+    // nothing to build, so no toolchain to look for
+    if !steps
+        .iter()
+        .any(|step| matches!(step.selection, Selection::Build(_)))
+    {
+        return Ok(());
+    }
 
-            let mut interval = time::interval(Duration::from_millis(50));
-            let mut last_line = String::new();
+    let libc = provider_install(&config, &build_config, &packages, &steps, LIBC)?;
+    let cc = provider_install(&config, &build_config, &packages, &steps, CC)?;
+
+    // installed once everything is built
+    let replaced = solve::replaced(&packages, &steps)
+        .into_iter()
+        .flat_map(|(package, versions)| {
+            package
+                .versions()
+                .pairs()
+                .filter(move |(version, _path)| versions.contains(version))
+                .map(|(_version, path)| path.to_path_buf())
+        })
+        .collect::<Vec<_>>();
+
+    // fetch everything first, cached tarballs are checked again, and listed patches exist
+    for step in steps.iter() {
+        if let Selection::Build(entry) = &step.selection {
+            patch::check(&step.package, &entry.version).await?;
+
+            config
+                .download_file_checked(&entry.path, &entry.url, &step.package.checksums_for(entry))
+                .await?;
+        }
+    }
 
-            loop {
-                tokio::select! {
-                    _ = interval.tick() => {
-                        write!(config.shell(), "\r\x1b[K > extract {}", &last_line)?;
-                        config.shell().flush().await?;
-                    }
-                    line = lines.next_line() => if let Some(line) = line {
-                        match line? {
-                            Line::Err(line) => last_line = line,
-                            Line::Out(line) => last_line = line,
-                        }
-                    } else {
-                        break;
+    for Step { package, selection } in steps {
+        let entry = match selection {
+            Selection::Build(entry) => entry,
+            Selection::Installed(_) => continue,
+        };
+
+        header!(
+            config.shell(),
+            "{} {}/{}:{}",
+            config.shell().theme().command_paint("extracting"),
+            package.repository_id(),
+            package.package_id(),
+            &entry.version
+        )?;
+
+        let version_str = entry.version.to_string();
+        let build_dir = package.build_prefix().join(version_str);
+
+        let _ = build_dir.create_dir_all_async().await;
+        let mut command = Command::bsdtar();
+
+        command
+            .arg("xvf")
+            .arg(&entry.path)
+            .current_dir(&build_dir)
+            .home_dir("/")
+            .stderr(Stdio::piped())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped());
+
+        let mut child = command.spawn().await?;
+        let stdio = child.stdio()?.expect("stdio");
+        let mut lines = stdio.lines();
+
+        tokio::spawn(async move {
+            // TODO: Proper error handling!
+            let _ = child.wait().await;
+        });
+
+        write!(config.shell(), "\r\x1b[K > extract")?;
+        config.shell().flush().await?;
+
+        let mut interval = time::interval(Duration::from_millis(50));
+        let mut last_line = String::new();
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    write!(config.shell(), "\r\x1b[K > extract {}", &last_line)?;
+                    config.shell().flush().await?;
+                }
+                line = lines.next_line() => if let Some(line) = line {
+                    match line? {
+                        Line::Err(line) => last_line = line,
+                        Line::Out(line) => last_line = line,
                     }
+                } else {
+                    break;
                 }
             }
+        }
 
-            let repository_id = package.repository_id();
-            let package_id = package.package_id();
-            let version = &entry.version;
-            let target = build_config.target;
-            let target_str = target.as_str();
-            let version_str = version.to_string();
-            let version_str = version_str.as_str();
-
-            let destination = build_config
-                .prefix
-                .join(target_str)
-                .join(&repository_id)
-                .join(&package_id)
-                .join(&version_str);
+        let repository_id = package.repository_id();
+        let package_id = package.package_id();
+        let version = &entry.version;
+        let target = build_config.target;
+        let target_str = target.as_str();
+        let version_str = version.to_string();
+        let version_str = version_str.as_str();
+
+        let destination = build_config
+            .prefix
+            .join(target_str)
+            .join(&repository_id)
+            .join(&package_id)
+            .join(&version_str);
+
+        let libc_lib = libc.root.join("lib");
+        let dynamic_linker = format!(
+            "ld-linux-{}.so.2",
+            build_config.target.as_tuple().0.as_str()
+        );
+
+        let (runtime_dir, runtime) = compiler_runtime(&cc, target).await?;
+
+        let extract_dir = build_dir;
+        let mut source_dir = extract_dir.clone();
+        let mut dirs = extract_dir.read_dir_async().await?;
+
+        if let Some(dir) = dirs.try_next().await? {
+            source_dir = dir.path();
+        }
 
-            let libc_root = build_config
-                .prefix
-                .join(target_str)
-                .join(&repository_id)
-                .join("glibc")
-                .join("2.34.0");
+        // NOTE: autotools appears to be retarded
+        // compiler.file("/milk/x86_64-linux-gnu/core/glibc/2.34.0/lib/crti.o")
 
-            let libc_lib = libc_root.join("lib");
-            let dynamic_linker = format!("ld-linux-{}.so.2", build_config.target.as_tuple().0.as_str());
+        let mut compiler = Compiler::new();
+        let mut linker = Linker::new();
 
-            let _compiler_root = build_config
-                .prefix
-                .join(build_config.target.as_str())
-                .join(&repository_id)
-                .join("gcc")
-                .join("11.2.0");
+        compiler.opt_level("fast");
 
-            let current_dir = build_dir.clone();
-            let mut source_dir = current_dir.clone();
+        if matches!(build_config.target.as_tuple().0, Arch::i686 | Arch::x86_64) {
+            compiler.target_cpu("native");
+        }
 
-            let build_dir = if build_config.build_dir {
-                build_dir.join("build")
-            } else {
-                build_dir
-            };
+        compiler
+            .no_default_libs()
+            .no_start_files()
+            .pic()
+            .linker("lld")
+            .library_dir(&runtime_dir)
+            .library_dir(&libc_lib)
+            .file(libc_lib.join("crt1.o"))
+            .file(libc_lib.join("crtn.o"))
+            .link(&runtime)
+            .link("c")
+            .runtime_path(&libc_lib)
+            .dynamic_linker(libc_lib.join(&dynamic_linker));
+
+        linker
+            .runtime_path(&libc_lib)
+            .dynamic_linker(libc_lib.join(dynamic_linker));
+
+        let cflags = compiler
+            .as_slice()
+            .iter()
+            .map(|s| s.to_string_lossy())
+            .intersperse(Cow::Borrowed(" "))
+            .collect::<String>();
 
-            let mut dirs = current_dir.read_dir_async().await?;
+        let ldflags = linker
+            .as_slice()
+            .iter()
+            .map(|s| s.to_string_lossy())
+            .intersperse(Cow::Borrowed(" "))
+            .collect::<String>();
+
+        header!(
+            config.shell(),
+            "building {}/{}:{}",
+            package.repository_id(),
+            package.package_id(),
+            &version,
+        )?;
+
+        header!(
+            config.shell(),
+            "{} {}",
+            config.shell().theme().command_paint("destination"),
+            &destination
+        )?;
+        header!(
+            config.shell(),
+            "{} {}",
+            config.shell().theme().command_paint("CFLAGS"),
+            &cflags
+        )?;
+        header!(
+            config.shell(),
+            "{} {}",
+            config.shell().theme().command_paint("LDFLAGS"),
+            &ldflags
+        )?;
+
+        // Dependencies are built with their defaults.
+        let options = if is_requested(&config, &packages, &build_config.requirement, &package) {
+            Options::new(&package, &build_config.define, &build_config.include)?
+        } else {
+            Options::new(&package, &[], &[])?
+        };
+
+        let context = recipe::Context {
+            package_id,
+            target,
+            jobs: build_config.jobs,
+            destination: &destination,
+            extract_dir: &extract_dir,
+            source_dir: &source_dir,
+            build_dir: build_config.build_dir,
+            cflags: &cflags,
+            ldflags: &ldflags,
+            options: &options,
+        };
+
+        let patches = patch::apply(&config, &package, version, &source_dir).await?;
+
+        let flags = recipe::run(&config, &context, package.build()).await?;
+
+        Record {
+            patches,
+            options: options.values(),
+            flags,
+            sha256: Some(mix_config::checksum::sha256_file(&entry.path).await?),
+        }
+        .save(&destination)
+        .await?;
+    }
 
-            if let Some(dir) = dirs.try_next().await? {
-                source_dir = dir.path();
-            }
+    for path in replaced {
+        header!(
+            config.shell(),
+            "{} {}",
+            config.shell().theme().command_paint("replaced"),
+            &path
+        )?;
 
-            let _ = build_dir.create_dir_all_async().await;
+        tokio::fs::remove_dir_all(path).await?;
+    }
 
-            // NOTE: autotools appears to be retarded
-            // compiler.file("/milk/x86_64-linux-gnu/core/glibc/2.34.0/lib/crti.o")
+    Ok(())
+}
 
-            let mut compiler = Compiler::new();
-            let mut linker = Linker::new();
+/// Whether `package` is what `requirement` asks for, rather than one of its dependencies.
+///
+/// Of several packages providing `requirement`, only the one the solver picks is requested.
+fn is_requested(
+    config: &mix_config::Config,
+    packages: &Packages,
+    requirement: &Requirement,
+    package: &Package,
+) -> bool {
+    let matches = packages.matches(requirement).collect::<Vec<_>>();
 
-            compiler.opt_level("fast");
+    prefer(config, &matches).map_or(false, |(requested, _shadowed)| {
+        requested.repository_id() == package.repository_id()
+            && requested.package_id() == package.package_id()
+    })
+}
 
-            if matches!(
-                build_config.target.as_tuple().0,
-                Arch::i686 | Arch::x86_64
-            ) {
-                compiler.target_cpu("native");
-            }
+/// Values of the build options each of `steps` is built with, or was installed with.
+///
+/// Fails when the requested package is already installed, but `-D` or `-I` ask for other values.
+async fn step_options(
+    config: &mix_config::Config,
+    build_config: &Config,
+    packages: &Packages,
+    steps: &[Step],
+) -> Result<Vec<BTreeMap<String, Value>>> {
+    let mut values = Vec::new();
 
-            compiler
-                .no_default_libs()
-                .no_start_files()
-                .pic()
-                .linker("lld")
-                .library_dir(
-                    "/milk/x86_64-linux-gnu/core/gcc/11.2.0/lib/gcc/x86_64-pc-linux-gnu/11.2.0",
-                )
-                .library_dir(&libc_lib)
-                .file(libc_lib.join("crt1.o"))
-                .file(libc_lib.join("crtn.o"))
-                .link("gcc")
-                .link("c")
-                .runtime_path(&libc_lib)
-                .dynamic_linker(libc_lib.join(&dynamic_linker));
-
-            linker
-                .runtime_path(&libc_lib)
-                .dynamic_linker(libc_lib.join(dynamic_linker));
-
-            let cflags = compiler
-                .as_slice()
-                .iter()
-                .map(|s| s.to_string_lossy())
-                .intersperse(Cow::Borrowed(" "))
-                .collect::<String>();
-
-            let ldflags = linker
-                .as_slice()
-                .iter()
-                .map(|s| s.to_string_lossy())
-                .intersperse(Cow::Borrowed(" "))
-                .collect::<String>();
-
-            //writeln!(config.shell(), "{:?}", package);
-            //writeln!(config.shell(), "installed: {:?}", package.installed());
-
-            header!(
-                config.shell(),
-                "building {}/{}:{}",
-                package.repository_id(),
-                package.package_id(),
-                &version,
-            )?;
-
-            header!(
-                config.shell(),
-                "{} {}",
-                config.shell().theme().command_paint("destination"),
-                &destination
-            )?;
-            header!(
-                config.shell(),
-                "{} {}",
-                config.shell().theme().command_paint("build"),
-                &build_dir
-            )?;
-            header!(
-                config.shell(),
-                "{} {}",
-                config.shell().theme().command_paint("CFLAGS"),
-                &cflags
-            )?;
-            header!(
-                config.shell(),
-                "{} {}",
-                config.shell().theme().command_paint("LDFLAGS"),
-                &ldflags
-            )?;
-
-            let build = configs::System::new(package_id, &source_dir).await;
-
-            for (_name, build_config) in build.config.iter() {
-                header!(
-                    config.shell(),
-                    "{} {}",
-                    config.shell().theme().command_paint("found"),
-                    build_config
-                )?;
-            }
+    for Step { package, selection } in steps.iter() {
+        let requested = is_requested(config, packages, &build_config.requirement, package);
+        let version = match selection {
+            Selection::Build(_entry) if requested => {
+                let options = Options::new(package, &build_config.define, &build_config.include)?;
 
-            if let Some((Some(bootstrap), _)) = build.get_autotools() {
-                let mut command = Command::new(bootstrap);
-
-                command
-                    .c_compiler(CLANG)
-                    .c_flags(&cflags)
-                    .cxx_compiler(CLANGXX)
-                    .cxx_flags(&cflags)
-                    .current_dir(&source_dir)
-                    .home_dir(&current_dir)
-                    .lang(EN_US)
-                    .linker(LLD)
-                    .linker_flags(&ldflags)
-                    .stderr(Stdio::piped())
-                    .stdin(Stdio::null())
-                    .stdout(Stdio::piped());
-
-                let mut child = command.spawn().await?;
-                let stdio = child.stdio()?.expect("stdio");
-                let mut lines = stdio.lines();
-
-                tokio::spawn(async move {
-                    // TODO: Proper error handling!
-                    let _ = child.wait().await;
-                });
-
-                copy_output(&config, "config", &mut lines).await?;
-            }
+                values.push(options.values());
 
-            if let Some(autogen) = build.get_autogen() {
-                let mut command = Command::new(autogen);
-
-                command
-                    //.env_clear()
-                    //.c_compiler(GCC)
-                    //.c_flags(&cflags)
-                    //.cxx_compiler(GXX)
-                    //.cxx_flags(&cflags)
-                    .current_dir(&source_dir)
-                    .home_dir(&current_dir)
-                    .lang(EN_US)
-                    //.linker(LD)
-                    //.linker_flags(&ldflags)
-                    //.paths(["/milk/global/bin", "/bin", "/sbin", "/usr/bin", "/usr/sbin"])
-                    .stderr(Stdio::piped())
-                    .stdin(Stdio::null())
-                    .stdout(Stdio::piped());
-
-                let mut child = command.spawn().await?;
-                let stdio = child.stdio()?.expect("stdio");
-                let mut lines = stdio.lines();
-
-                tokio::spawn(async move {
-                    // TODO: Proper error handling!
-                    let _ = child.wait().await;
-                });
-
-                copy_output(&config, "config", &mut lines).await?;
+                continue;
             }
+            Selection::Build(_entry) => {
+                values.push(Options::new(package, &[], &[])?.values());
 
-            if build.has_configure_ac() {
-                let mut command = Command::autoreconf();
-
-                command
-                    .arg("-f")
-                    .arg("-i")
-                    //.env_clear()
-                    //.c_compiler(GCC)
-                    //.c_flags(&cflags)
-                    //.cxx_compiler(GXX)
-                    //.cxx_flags(&cflags)
-                    .current_dir(&source_dir)
-                    .home_dir(&current_dir)
-                    .lang(EN_US)
-                    //.linker(LD)
-                    //.linker_flags(&ldflags)
-                    //.paths(["/milk/global/bin", "/bin", "/sbin", "/usr/bin", "/usr/sbin"])
-                    .stderr(Stdio::piped())
-                    .stdin(Stdio::null())
-                    .stdout(Stdio::piped());
-
-                let mut child = command.spawn().await?;
-                let stdio = child.stdio()?.expect("stdio");
-                let mut lines = stdio.lines();
-
-                tokio::spawn(async move {
-                    // TODO: Proper error handling!
-                    let _ = child.wait().await;
-                });
-
-                copy_output(&config, "config", &mut lines).await?;
+                continue;
             }
-
-            let build = configs::System::new(package_id, &source_dir).await;
-
-            if let Some((_, Some(configure))) = build.get_autotools() {
-                let mut command = Command::new(configure);
-
-                command
-                    .arg(format!("--prefix={}", &destination))
-                    //.arg(format!("--build={}", &target.as_gnu_str()))
-                    //.arg(format!("--host={}", &target.as_gnu_str()))
-                    .arg(format!("--target={}", &target.as_str()))
-                    //.env_clear()
-                    //.c_compiler(GCC)
-                    //.c_flags(&cflags)
-                    //.cxx_compiler(GXX)
-                    //.cxx_flags(&cflags)
-                    .current_dir(&build_dir)
-                    .home_dir(&current_dir)
-                    .lang(EN_US)
-                    //.linker(LD)
-                    //.linker_flags(&ldflags)
-                    //.paths(["/milk/global/bin", "/bin", "/sbin", "/usr/bin", "/usr/sbin"])
-                    .stderr(Stdio::piped())
-                    .stdin(Stdio::null())
-                    .stdout(Stdio::piped());
-
-                let mut child = command.spawn().await?;
-                let stdio = child.stdio()?.expect("stdio");
-                let mut lines = stdio.lines();
-
-                tokio::spawn(async move {
-                    // TODO: Proper error handling!
-                    let _ = child.wait().await;
-                });
-
-                copy_output(&config, "config", &mut lines).await?;
+            Selection::Installed(version) => version,
+        };
+
+        let mut installed = Options::new(package, &[], &[])?.values();
+        let record = match package
+            .versions()
+            .pairs()
+            .find(|(installed, _path)| *installed == version)
+        {
+            Some((_version, path)) => Record::load(path).await?,
+            None => None,
+        };
+
+        for (name, value) in record.map(|record| record.options).unwrap_or_default() {
+            if let Some(chosen) = installed.get_mut(&name) {
+                *chosen = value;
             }
+        }
 
-            let mut command = Command::make();
-
-            command
-                .arg(format!("-j{}", build_config.jobs))
-                //.env_clear()
-                //.c_compiler(GCC)
-                //.c_flags(&cflags)
-                //.cxx_compiler(GXX)
-                //.cxx_flags(&cflags)
-                .current_dir(&build_dir)
-                .home_dir(&current_dir)
-                .lang(EN_US)
-                //.linker(LD)
-                //.linker_flags(&ldflags)
-                //.paths(["/milk/global/bin", "/bin", "/sbin", "/usr/bin", "/usr/sbin"])
-                .stderr(Stdio::piped())
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped());
-
-            let mut child = command.spawn().await?;
-            let stdio = child.stdio()?.expect("stdio");
-            let mut lines = stdio.lines();
-
-            let handle: tokio::task::JoinHandle<Result<()>> = tokio::spawn(async move {
-                // TODO: Proper error handling!
-                let exit_code = child.wait().await?;
-
-                if !exit_code.success() {
-                    println!("bad!");
-
-                    return Err("bad".into());
-                }
+        if requested {
+            check_installed(build_config, package, version, &installed)?;
+        }
 
-                Ok(())
-            });
-
-            copy_output(&config, "build", &mut lines).await?;
-
-            let mut command = Command::make();
-
-            command
-                .arg("install")
-                .arg(format!("-j{}", build_config.jobs))
-                //.env_clear()
-                //.c_compiler(GCC)
-                //.c_flags(&cflags)
-                //.cxx_compiler(GXX)
-                //.cxx_flags(&cflags)
-                .current_dir(&build_dir)
-                .home_dir(&current_dir)
-                .lang(EN_US)
-                //.linker(LD)
-                //.linker_flags(&ldflags)
-                //.paths(["/milk/global/bin", "/bin", "/sbin", "/usr/bin", "/usr/sbin"])
-                .stderr(Stdio::piped())
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped());
-
-            let mut child = command.spawn().await?;
-            let stdio = child.stdio()?.expect("stdio");
-            let mut lines = stdio.lines();
-
-            let handle: tokio::task::JoinHandle<Result<()>> = tokio::spawn(async move {
-                // TODO: Proper error handling!
-                let exit_code = child.wait().await?;
-
-                if !exit_code.success() {
-                    println!("bad!");
-
-                    return Err("bad".into());
-                }
+        values.push(installed);
+    }
 
-                Ok(())
-            });
+    Ok(values)
+}
 
-            copy_output(&config, "install", &mut lines).await?;
+/// Fail unless `installed`, the option values `package` was built with, agree with `-D` and `-I`.
+fn check_installed(
+    build_config: &Config,
+    package: &Package,
+    version: &Version,
+    installed: &BTreeMap<String, Value>,
+) -> Result<()> {
+    let name = format!(
+        "{}/{} {}",
+        package.repository_id(),
+        package.package_id(),
+        version
+    );
 
-            if !source_dir.is_dir_async().await {
-                continue;
+    let given = build_config
+        .define
+        .iter()
+        .chain(build_config.include.iter());
+
+    for (key, value) in given {
+        match installed.get(key) {
+            Some(installed) if installed == value => {}
+            Some(installed) => {
+                return Err(format!(
+                    "{} is installed with {}={}, remove it to build it with {}={}",
+                    name, key, installed, key, value
+                )
+                .into());
+            }
+            None => {
+                return Err(format!(
+                    "{} is installed, and `{}` only applies when it's built",
+                    name, key
+                )
+                .into());
             }
+        }
+    }
 
-            let mut command = Command::make();
-
-            command
-                .arg(format!("-j{}", build_config.jobs))
-                //.env_clear()
-                //.c_compiler(GCC)
-                //.c_flags(&cflags)
-                //.cxx_compiler(GXX)
-                //.cxx_flags(&cflags)
-                .current_dir(&source_dir)
-                .home_dir(&current_dir)
-                .lang(EN_US)
-                //.linker(LD)
-                //.linker_flags(&ldflags)
-                //.paths(["/milk/global/bin", "/bin", "/sbin", "/usr/bin", "/usr/sbin"])
-                .stderr(Stdio::piped())
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped());
-
-            let mut child = command.spawn().await?;
-            let stdio = child.stdio()?.expect("stdio");
-            let mut lines = stdio.lines();
-
-            let handle: tokio::task::JoinHandle<Result<()>> = tokio::spawn(async move {
-                // TODO: Proper error handling!
-                let exit_code = child.wait().await?;
-
-                if !exit_code.success() {
-                    println!("bad!");
-
-                    return Err("bad".into());
-                }
+    Ok(())
+}
 
-                Ok(())
-            });
-
-            copy_output(&config, "build", &mut lines).await?;
-
-            let mut command = Command::make();
-
-            command
-                .arg("install")
-                .arg(format!("-j{}", build_config.jobs))
-                //.env_clear()
-                //.c_compiler(GCC)
-                //.c_flags(&cflags)
-                //.cxx_compiler(GXX)
-                //.cxx_flags(&cflags)
-                .current_dir(&source_dir)
-                .home_dir(&current_dir)
-                .lang(EN_US)
-                //.linker(LD)
-                //.linker_flags(&ldflags)
-                //.paths(["/milk/global/bin", "/bin", "/sbin", "/usr/bin", "/usr/sbin"])
-                .stderr(Stdio::piped())
-                .stdin(Stdio::null())
-                .stdout(Stdio::piped());
-
-            let mut child = command.spawn().await?;
-            let stdio = child.stdio()?.expect("stdio");
-            let mut lines = stdio.lines();
-
-            let handle: tokio::task::JoinHandle<Result<()>> = tokio::spawn(async move {
-                // TODO: Proper error handling!
-                let exit_code = child.wait().await?;
-
-                if !exit_code.success() {
-                    println!("bad!");
-
-                    return Err("bad".into());
-                }
+/// Spawn `command`, copy its output to the shell, and fail if it does.
+async fn execute(config: &mix_config::Config, prefix: &str, command: &mut Command) -> Result<()> {
+    let mut child = command.spawn().await?;
+    let stdio = child.stdio()?.expect("stdio");
+    let mut lines = stdio.lines();
+    let handle = tokio::spawn(async move { child.wait().await });
 
-                Ok(())
-            });
+    copy_output(config, prefix, &mut lines).await?;
 
-            let copy_output = copy_output(&config, "install", &mut lines);
+    let status = handle.await??;
 
-            tokio::select! {
-                _ = copy_output => {},
-                _ = handle => {},
-            };
-        }
+    if !status.success() {
+        return Err(format!("{} failed, {}", prefix, status).into());
     }
 
     Ok(())
@@ -0,0 +1,284 @@
+use crate::record::Record;
+use crate::solve::{Selection, Step};
+use crate::{Result, Value};
+use mix_atom::Requirement;
+use mix_config::checksum::Checksum;
+use mix_id::{PackageId, RepositoryId};
+use mix_packages::Package;
+use mix_shell::{header, AsyncWrite};
+use mix_version::Version;
+use path::Path;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use tokio::process::Command;
+use url::Url;
+
+/// An exact install set, written by `mix add --lock` and reproduced by `mix add --locked`.
+#[derive(Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Lock {
+    #[serde(default, rename = "package")]
+    pub packages: Vec<Locked>,
+}
+
+/// A package pinned by a lockfile.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Locked {
+    pub repository: RepositoryId,
+    pub package: PackageId,
+    pub version: Version,
+    pub url: Url,
+    pub sha256: String,
+
+    /// Commit of the repository checkout the manifest came from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+
+    /// Values of the build options the package declares.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, Value>,
+}
+
+impl Lock {
+    /// Read and parse the lockfile at `path`.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = path
+            .read_to_string_async()
+            .await
+            .map_err(|error| format!("unable to read {}: {}", path, error))?;
+
+        Ok(serde_yaml::from_str(text.as_str())?)
+    }
+
+    /// Serialize and write this lockfile to `path`.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let text = serde_yaml::to_string(self)?;
+
+        path.as_ref().write_async(text.as_str()).await?;
+
+        Ok(())
+    }
+
+    /// Requirements pinning every locked package to its exact version, to solve with as
+    /// constraints rather than as requests.
+    pub fn pins(&self) -> Result<Vec<Requirement>> {
+        self.packages
+            .iter()
+            .map(|locked| {
+                Ok(Requirement {
+                    repository_id: Some(locked.repository.clone()),
+                    package_id: locked.package.clone(),
+                    requirement: mix_version::Requirement::parse(&format!("={}", locked.version))?,
+                })
+            })
+            .collect()
+    }
+
+    /// Record `steps` built with `options`.
+    ///
+    /// Tarball checksums come from the manifest, or the install record of installed steps. Only
+    /// tarballs with neither are downloaded, if they aren't cached yet, to checksum them.
+    pub async fn from_steps(
+        config: &mix_config::Config,
+        steps: &[Step],
+        options: &[BTreeMap<String, Value>],
+    ) -> Result<Self> {
+        let mut commits = HashMap::new();
+        let mut packages = Vec::new();
+
+        for (Step { package, selection }, options) in steps.iter().zip(options) {
+            let entry = match selection {
+                Selection::Build(entry) => entry.clone(),
+                Selection::Installed(version) => package
+                    .upstream_versions(config)
+                    .await
+                    .iter()
+                    .find(|entry| &entry.version == version)
+                    .cloned()
+                    .ok_or_else(|| {
+                        format!(
+                            "no upstream release of {}/{} {} to lock",
+                            package.repository_id(),
+                            package.package_id(),
+                            version
+                        )
+                    })?,
+            };
+
+            let checksums = package.checksums_for(&entry);
+            let declared = checksums.iter().find_map(|checksum| match checksum {
+                Checksum::Sha256(digest) => Some(digest.clone()),
+                Checksum::Blake3(_) => None,
+            });
+
+            let recorded = match selection {
+                Selection::Installed(version) => recorded_sha256(package, version).await?,
+                Selection::Build(_) => None,
+            };
+
+            let sha256 = match declared.or(recorded) {
+                Some(sha256) => sha256,
+                None => {
+                    config
+                        .download_file_checked(&entry.path, &entry.url, &checksums)
+                        .await?;
+
+                    mix_config::checksum::sha256_file(&entry.path).await?
+                }
+            };
+
+            if !commits.contains_key(package.repository_id()) {
+                let commit = repository_commit(config, package.repository_id()).await;
+
+                commits.insert(package.repository_id().clone(), commit);
+            }
+
+            packages.push(Locked {
+                repository: package.repository_id().clone(),
+                package: package.package_id().clone(),
+                version: entry.version,
+                url: entry.url,
+                sha256,
+                commit: commits[package.repository_id()].clone(),
+                options: options.clone(),
+            });
+        }
+
+        Ok(Self { packages })
+    }
+
+    /// Report every way `actual` differs from this lockfile.
+    pub async fn verify(&self, config: &mix_config::Config, actual: &Lock) -> Result<()> {
+        let shell = config.shell();
+        let mut drifts = Vec::new();
+
+        for expected in self.packages.iter() {
+            let found = actual.packages.iter().find(|locked| {
+                locked.repository == expected.repository && locked.package == expected.package
+            });
+
+            let locked = match found {
+                Some(locked) => locked,
+                None => {
+                    drifts.push(format!(
+                        "{}/{} is locked but no longer required",
+                        expected.repository, expected.package
+                    ));
+
+                    continue;
+                }
+            };
+
+            let name = format!("{}/{}", expected.repository, expected.package);
+
+            if locked.version != expected.version {
+                drifts.push(format!(
+                    "{} resolved to {}, locked at {}",
+                    name, locked.version, expected.version
+                ));
+            }
+
+            if locked.url != expected.url {
+                drifts.push(format!(
+                    "{} source is {}, locked at {}",
+                    name, locked.url, expected.url
+                ));
+            }
+
+            if locked.sha256 != expected.sha256 {
+                drifts.push(format!(
+                    "{} tarball checksum is {}, locked at {}",
+                    name, locked.sha256, expected.sha256
+                ));
+            }
+
+            if locked.options != expected.options {
+                drifts.push(format!(
+                    "{} is built with {}, locked with {}",
+                    name,
+                    display_options(&locked.options),
+                    display_options(&expected.options)
+                ));
+            }
+
+            if expected.commit.is_some() && locked.commit != expected.commit {
+                drifts.push(format!(
+                    "{} repository is at {}, locked at {}",
+                    name,
+                    locked.commit.as_deref().unwrap_or("an unknown commit"),
+                    expected.commit.as_deref().unwrap_or_default()
+                ));
+            }
+        }
+
+        for locked in actual.packages.iter() {
+            let known = self.packages.iter().any(|expected| {
+                locked.repository == expected.repository && locked.package == expected.package
+            });
+
+            if !known {
+                drifts.push(format!(
+                    "{}/{} is required but not locked",
+                    locked.repository, locked.package
+                ));
+            }
+        }
+
+        for drift in drifts.iter() {
+            header!(shell, "{} {}", shell.theme().error_paint("drift"), drift)?;
+        }
+
+        if !drifts.is_empty() {
+            return Err(format!("{} difference(s) from the lockfile", drifts.len()).into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Tarball checksum in the install record of `version` of `package`.
+async fn recorded_sha256(package: &Package, version: &Version) -> Result<Option<String>> {
+    let path = package
+        .versions()
+        .pairs()
+        .find(|(installed, _path)| *installed == version)
+        .map(|(_version, path)| path);
+
+    Ok(match path {
+        Some(path) => Record::load(path).await?.and_then(|record| record.sha256),
+        None => None,
+    })
+}
+
+/// `name=value` pairs, separated by spaces.
+fn display_options(options: &BTreeMap<String, Value>) -> String {
+    if options.is_empty() {
+        return "no options".to_string();
+    }
+
+    options
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `HEAD` of a repository checkout, if it's a git repository.
+async fn repository_commit(
+    config: &mix_config::Config,
+    repository_id: &RepositoryId,
+) -> Option<String> {
+    let output = Command::new("git")
+        .arg("rev-parse")
+        .arg("HEAD")
+        .current_dir(config.repos_prefix().join(repository_id.as_str()))
+        .output()
+        .await
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
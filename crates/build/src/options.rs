@@ -0,0 +1,323 @@
+use crate::{Result, Value};
+use mix_manifest::{Autotools, System};
+use mix_packages::Package;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Define,
+    Include,
+}
+
+/// The options a package is built with.
+#[derive(Debug)]
+pub(crate) struct Options {
+    /// Every option the manifest declares by name, and whether `-D` or `-I` set it.
+    declared: BTreeMap<String, (mix_manifest::BuildOption, Value, bool)>,
+
+    /// Keys given with `-D` or `-I` which the manifest doesn't declare.
+    undeclared: Vec<(Kind, String, Value)>,
+}
+
+impl Options {
+    /// The declared defaults of `package`, overridden by `define` and `include`.
+    ///
+    /// Options mapped to `--enable` have to be set with `define`, those mapped to `--with` with
+    /// `include`.
+    pub fn new(
+        package: &Package,
+        define: &[(String, Value)],
+        include: &[(String, Value)],
+    ) -> Result<Self> {
+        let mut declared = package
+            .options()
+            .iter()
+            .map(|(name, option)| {
+                let value = option.default.clone();
+
+                (name.clone(), (option.clone(), value, false))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut undeclared = Vec::new();
+        let given = define
+            .iter()
+            .map(|(key, value)| (Kind::Define, key, value))
+            .chain(
+                include
+                    .iter()
+                    .map(|(key, value)| (Kind::Include, key, value)),
+            );
+
+        for (kind, key, value) in given {
+            let (option, chosen, given) = match declared.get_mut(key) {
+                Some(declared) => declared,
+                None => {
+                    undeclared.push((kind, key.clone(), value.clone()));
+
+                    continue;
+                }
+            };
+
+            match (kind, &option.autotools) {
+                (Kind::Include, Some(Autotools::Enable(name))) => {
+                    return Err(format!(
+                        "`{}` is passed as --enable-{}, set it with -D instead of -I",
+                        key, name
+                    )
+                    .into());
+                }
+                (Kind::Define, Some(Autotools::With(name))) => {
+                    return Err(format!(
+                        "`{}` is passed as --with-{}, set it with -I instead of -D",
+                        key, name
+                    )
+                    .into());
+                }
+                _ => {}
+            }
+
+            *chosen = value.clone();
+            *given = true;
+        }
+
+        Ok(Self {
+            declared,
+            undeclared,
+        })
+    }
+
+    /// Chosen values of the declared options.
+    pub fn values(&self) -> BTreeMap<String, Value> {
+        self.declared
+            .iter()
+            .map(|(name, (_option, value, _given))| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Arguments for `system` to configure with.
+    ///
+    /// Options set with `-D` or `-I` which `system` has no mapping for are an error, unless
+    /// `custom` configure commands see them in the environment instead.
+    pub fn args(&self, system: Option<System>, custom: bool) -> Result<Vec<String>> {
+        let mut args = Vec::new();
+
+        for (name, (option, value, given)) in self.declared.iter() {
+            let arg = match system {
+                Some(System::Autotools) => {
+                    option.autotools.as_ref().map(|autotools| match autotools {
+                        Autotools::Enable(name) => autotools_arg(Kind::Define, name, value),
+                        Autotools::With(name) => autotools_arg(Kind::Include, name, value),
+                    })
+                }
+                Some(System::Cmake) => option.cmake.as_ref().map(|name| cmake_arg(name, value)),
+                Some(System::Meson) => option.meson.as_ref().map(|name| meson_arg(name, value)),
+                _ => None,
+            };
+
+            match arg {
+                Some(arg) => args.push(arg),
+                None if *given && !custom => {
+                    let system = system.map_or("custom".to_string(), |system| system.to_string());
+
+                    return Err(format!(
+                        "`{}` declares no {} mapping, and can't be passed to a {} build",
+                        name, system, system
+                    )
+                    .into());
+                }
+                None => {}
+            }
+        }
+
+        for (kind, key, value) in self.undeclared.iter() {
+            let arg = match system {
+                Some(System::Autotools) => autotools_arg(*kind, key, value),
+                Some(System::Cmake) => cmake_arg(key, value),
+                Some(System::Meson) => meson_arg(key, value),
+                _ => {
+                    let system = system.map_or("custom".to_string(), |system| system.to_string());
+
+                    return Err(format!(
+                        "`{}` is not a declared option, and can't be passed to a {} build",
+                        key, system
+                    )
+                    .into());
+                }
+            };
+
+            args.push(arg);
+        }
+
+        Ok(args)
+    }
+
+    /// Environment variables custom phase commands see, such as `OPTION_TLS=true`.
+    pub fn env(&self) -> Vec<(String, String)> {
+        self.declared
+            .iter()
+            .map(|(name, (_option, value, _given))| {
+                let name = format!("OPTION_{}", name.to_uppercase().replace('-', "_"));
+
+                (name, value.to_string())
+            })
+            .collect()
+    }
+}
+
+fn autotools_arg(kind: Kind, name: &str, value: &Value) -> String {
+    match (kind, value) {
+        (Kind::Define, Value::Bool(true)) => format!("--enable-{}", name),
+        (Kind::Define, Value::Bool(false)) => format!("--disable-{}", name),
+        (Kind::Define, Value::String(value)) => format!("--enable-{}={}", name, value),
+        (Kind::Include, Value::Bool(true)) => format!("--with-{}", name),
+        (Kind::Include, Value::Bool(false)) => format!("--without-{}", name),
+        (Kind::Include, Value::String(value)) => format!("--with-{}={}", name, value),
+    }
+}
+
+fn cmake_arg(name: &str, value: &Value) -> String {
+    match value {
+        Value::Bool(true) => format!("-D{}=ON", name),
+        Value::Bool(false) => format!("-D{}=OFF", name),
+        Value::String(value) => format!("-D{}={}", name, value),
+    }
+}
+
+fn meson_arg(name: &str, value: &Value) -> String {
+    format!("-D{}={}", name, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Options;
+    use crate::Value;
+    use mix_id::{PackageId, RepositoryId};
+    use mix_manifest::{Autotools, Build, BuildOption, System};
+    use mix_packages::{Package, PackageRef};
+    use mix_source::Sources;
+    use mix_version::Versions;
+    use path::Path;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    /// `core/curl`, declaring `tls` as `--with-openssl` and `ipv6` as `--enable-ipv6`.
+    fn curl() -> Package {
+        let tls = BuildOption {
+            autotools: Some(Autotools::With("openssl".to_string())),
+            cmake: Some("CURL_USE_OPENSSL".to_string()),
+            ..BuildOption::default()
+        };
+
+        let ipv6 = BuildOption {
+            default: Value::Bool(true),
+            autotools: Some(Autotools::Enable("ipv6".to_string())),
+            ..BuildOption::default()
+        };
+
+        Package::from(PackageRef {
+            repository_id: RepositoryId::CORE.clone(),
+            package_id: PackageId::try_from("curl").unwrap(),
+            versions: Versions::new(),
+            description: None,
+            homepage: None,
+            license: None,
+            maintainers: Vec::new(),
+            keywords: BTreeSet::new(),
+            dependencies: BTreeSet::new(),
+            build_dependencies: BTreeSet::new(),
+            test_dependencies: BTreeSet::new(),
+            bootstrap: BTreeSet::new(),
+            sources: Sources::new("/cache"),
+            provides: BTreeSet::new(),
+            conflicts: BTreeSet::new(),
+            replaces: BTreeSet::new(),
+            targets: BTreeMap::new(),
+            build: Build::default(),
+            options: [("tls".to_string(), tls), ("ipv6".to_string(), ipv6)]
+                .into_iter()
+                .collect(),
+            patches: None,
+            checksums: Vec::new(),
+            manifest_path: Path::new("/repos/core/curl").join("manifest.yml"),
+            patches_dir: Path::new("/repos/core/curl").join("patches"),
+            build_prefix: Path::new("/build/core").join("curl"),
+        })
+    }
+
+    fn set(key: &str, value: Value) -> Vec<(String, Value)> {
+        vec![(key.to_string(), value)]
+    }
+
+    #[test]
+    fn defaults() {
+        let options = Options::new(&curl(), &[], &[]).unwrap();
+        let args = options.args(Some(System::Autotools), false).unwrap();
+
+        assert_eq!(args, ["--enable-ipv6", "--without-openssl"]);
+    }
+
+    #[test]
+    fn define_maps_to_enable() {
+        let define = set("ipv6", Value::Bool(false));
+        let options = Options::new(&curl(), &define, &[]).unwrap();
+        let args = options.args(Some(System::Autotools), false).unwrap();
+
+        assert_eq!(args, ["--disable-ipv6", "--without-openssl"]);
+        assert!(Options::new(&curl(), &[], &define).is_err());
+    }
+
+    #[test]
+    fn include_maps_to_with() {
+        let include = set("tls", Value::String("/opt/ssl".to_string()));
+        let options = Options::new(&curl(), &[], &include).unwrap();
+        let args = options.args(Some(System::Autotools), false).unwrap();
+
+        assert_eq!(args, ["--enable-ipv6", "--with-openssl=/opt/ssl"]);
+        assert!(Options::new(&curl(), &include, &[]).is_err());
+    }
+
+    #[test]
+    fn undeclared_keep_their_flag() {
+        let define = set("static", Value::Bool(true));
+        let include = set("zlib", Value::Bool(false));
+        let options = Options::new(&curl(), &define, &include).unwrap();
+        let args = options.args(Some(System::Autotools), false).unwrap();
+
+        assert_eq!(
+            args,
+            [
+                "--enable-ipv6",
+                "--without-openssl",
+                "--enable-static",
+                "--without-zlib"
+            ]
+        );
+    }
+
+    #[test]
+    fn given_option_without_mapping() {
+        let define = set("ipv6", Value::Bool(false));
+        let options = Options::new(&curl(), &define, &[]).unwrap();
+
+        // `ipv6` declares no CMake variable.
+        assert!(options.args(Some(System::Cmake), false).is_err());
+        assert_eq!(
+            options.args(Some(System::Cmake), true).unwrap(),
+            ["-DCURL_USE_OPENSSL=OFF"]
+        );
+    }
+
+    #[test]
+    fn env_names_every_option() {
+        let include = set("tls", Value::Bool(true));
+        let options = Options::new(&curl(), &[], &include).unwrap();
+
+        assert_eq!(
+            options.env(),
+            [
+                ("OPTION_IPV6".to_string(), "true".to_string()),
+                ("OPTION_TLS".to_string(), "true".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use crate::process::Command;
+use crate::record::Applied;
+use crate::{execute, Result};
+use command_extra::Stdio;
+use futures_util::stream::TryStreamExt;
+use mix_manifest::Patch;
+use mix_packages::Package;
+use mix_shell::{header, AsyncWrite};
+use mix_version::Version;
+use path::Path;
+
+const EXTENSIONS: [&str; 2] = ["diff", "patch"];
+
+/// Apply the patches of `package` selected for `version` to `source_dir`, in order.
+pub(crate) async fn apply(
+    config: &mix_config::Config,
+    package: &Package,
+    version: &Version,
+    source_dir: &Path,
+) -> Result<Vec<Applied>> {
+    let mut applied = Vec::new();
+
+    for patch in select(package, version).await? {
+        let path = package.patches_dir().join(&patch.file);
+        let sha256 = mix_config::checksum::sha256_file(&path).await?;
+
+        header!(
+            config.shell(),
+            "{} {}",
+            config.shell().theme().command_paint("patch"),
+            &patch.file
+        )?;
+
+        let mut command = Command::patch();
+
+        command
+            .arg("--batch")
+            .arg("--forward")
+            .arg(format!("-p{}", patch.strip))
+            .arg("-i")
+            .arg(&path)
+            .current_dir(source_dir)
+            .stderr(Stdio::piped())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped());
+
+        execute(config, "patch", &mut command)
+            .await
+            .map_err(|error| {
+                format!(
+                    "{} does not apply to {}/{} {}: {}",
+                    &patch.file,
+                    package.repository_id(),
+                    package.package_id(),
+                    version,
+                    error
+                )
+            })?;
+
+        applied.push(Applied {
+            file: patch.file,
+            sha256,
+        });
+    }
+
+    Ok(applied)
+}
+
+/// Fail unless every patch the manifest lists for `version` exists, before anything is built.
+pub(crate) async fn check(package: &Package, version: &Version) -> Result<()> {
+    for patch in select(package, version).await? {
+        let path = package.patches_dir().join(&patch.file);
+
+        if !path.exists_async().await {
+            return Err(format!(
+                "{}/{} lists patch {}, which doesn't exist",
+                package.repository_id(),
+                package.package_id(),
+                path
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// The patches the manifest lists for `version`, or every patch in the directory by file name.
+async fn select(package: &Package, version: &Version) -> Result<Vec<Patch>> {
+    if let Some(patches) = package.patches() {
+        return Ok(patches
+            .iter()
+            .filter(|patch| version.matches(&patch.version))
+            .cloned()
+            .collect());
+    }
+
+    let patches_dir = package.patches_dir();
+
+    if !patches_dir.is_dir_async().await {
+        return Ok(Vec::new());
+    }
+
+    let mut files = patches_dir
+        .read_dir_async()
+        .await?
+        .try_filter_map(|entry| async move {
+            let path = entry.path();
+            let file = path
+                .file_name()
+                .map(|name| name.as_str().to_string())
+                .filter(|name| {
+                    EXTENSIONS
+                        .iter()
+                        .any(|extension| name.ends_with(&format!(".{}", extension)))
+                });
+
+            Ok(file)
+        })
+        .try_collect::<Vec<_>>()
+        .await?;
+
+    files.sort();
+
+    Ok(files
+        .into_iter()
+        .map(|file| Patch {
+            file,
+            version: mix_version::Requirement::star(),
+            strip: 1,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check, select};
+    use mix_id::{PackageId, RepositoryId};
+    use mix_manifest::Patch;
+    use mix_packages::{Package, PackageRef};
+    use mix_version::Version;
+    use path::Path;
+
+    /// An orphaned `core/<name>` in a fresh temporary prefix, with `files` in its `patches/`.
+    async fn package(name: &str, files: &[&str]) -> PackageRef {
+        let prefix = Path::new(std::env::temp_dir().to_str().unwrap()).join(format!(
+            "mix-patch-{}-{}",
+            name,
+            std::process::id()
+        ));
+
+        let _ = tokio::fs::remove_dir_all(&prefix).await;
+
+        prefix.create_dir_all_async().await.unwrap();
+
+        let config = mix_config::Config::new(&prefix).await.unwrap();
+        let package = PackageRef::new_orphaned(
+            config,
+            RepositoryId::CORE.clone(),
+            PackageId::try_from(name).unwrap(),
+        );
+
+        package.patches_dir.create_dir_all_async().await.unwrap();
+
+        for file in files {
+            package
+                .patches_dir
+                .join(file)
+                .write_async("")
+                .await
+                .unwrap();
+        }
+
+        package
+    }
+
+    fn patch(file: &str) -> Patch {
+        serde_yaml::from_str(&format!("file: {}", file)).unwrap()
+    }
+
+    fn files(patches: &[Patch]) -> Vec<&str> {
+        patches.iter().map(|patch| patch.file.as_str()).collect()
+    }
+
+    #[test]
+    fn file_outside_patches_dir() {
+        for file in ["../escape.patch", "nested/fix.patch", "..", "."] {
+            let text = format!("file: \"{}\"", file);
+
+            assert!(serde_yaml::from_str::<Patch>(&text).is_err(), "{}", file);
+        }
+
+        assert_eq!(patch("fix.patch").strip, 1);
+    }
+
+    #[tokio::test]
+    async fn listed_patch_missing() {
+        let mut package = package("missing", &["present.patch"]).await;
+
+        package.patches = Some(vec![patch("present.patch"), patch("absent.patch")]);
+
+        let package = Package::from(package);
+        let version = Version::parse("1.0.0").unwrap();
+
+        assert!(check(&package, &version).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn listed_patches_by_version() {
+        let mut package = package("listed", &["old.patch", "new.patch"]).await;
+        let mut old = patch("old.patch");
+
+        old.version = mix_version::Requirement::parse("<2").unwrap();
+        package.patches = Some(vec![patch("new.patch"), old]);
+
+        let package = Package::from(package);
+        let version = Version::parse("2.0.0").unwrap();
+
+        assert!(check(&package, &version).await.is_ok());
+        assert_eq!(
+            files(&select(&package, &version).await.unwrap()),
+            ["new.patch"]
+        );
+    }
+
+    #[tokio::test]
+    async fn every_patch_sorted_without_list() {
+        let files_on_disk = ["b.patch", "notes.txt", "a.diff", "c.patch.orig", "c.patch"];
+        let package = Package::from(package("unlisted", &files_on_disk).await);
+        let version = Version::parse("1.0.0").unwrap();
+        let patches = select(&package, &version).await.unwrap();
+
+        assert_eq!(files(&patches), ["a.diff", "b.patch", "c.patch"]);
+        assert!(patches.iter().all(|patch| patch.strip == 1));
+    }
+}
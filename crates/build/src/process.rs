@@ -16,6 +16,7 @@ pub mod program {
     pub(crate) const CMAKE: &str = "cmake";
     pub(crate) const MAKE: &str = "make";
     pub(crate) const MESON: &str = "meson";
+    pub(crate) const PATCH: &str = "patch";
     pub(crate) const SH: &str = "sh";
     pub(crate) const TAR: &str = "tar";
 }
@@ -74,6 +75,10 @@ impl Command {
         Self::new(program::MESON)
     }
 
+    pub fn patch() -> Self {
+        Self::new(program::PATCH)
+    }
+
     pub fn sh() -> Self {
         Self::new(program::SH)
     }
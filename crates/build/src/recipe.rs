@@ -0,0 +1,386 @@
+use crate::options::Options;
+use crate::process::Command;
+use crate::{configs, execute, Result};
+use crate::{CLANG, CLANGXX, EN_US, LLD};
+use command_extra::Stdio;
+use mix_id::PackageId;
+use mix_manifest::{Build, System};
+use mix_shell::{header, AsyncWrite};
+use mix_triple::Triple;
+use path::{Path, PathBuf};
+
+/// Everything a recipe needs to know about the package being built.
+pub(crate) struct Context<'a> {
+    pub package_id: &'a PackageId,
+    pub target: Triple,
+    pub jobs: usize,
+
+    /// Where the package is installed to.
+    pub destination: &'a Path,
+
+    /// Where the tarball was extracted, used as `HOME`.
+    pub extract_dir: &'a Path,
+    pub source_dir: &'a Path,
+
+    /// Whether `--build-dir` was given.
+    pub build_dir: bool,
+
+    pub cflags: &'a str,
+    pub ldflags: &'a str,
+
+    /// Build options, passed to configure.
+    pub options: &'a Options,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Phase {
+    Prepare,
+    Configure,
+    Compile,
+    Install,
+}
+
+impl Phase {
+    fn name(self) -> &'static str {
+        match self {
+            Phase::Prepare => "prepare",
+            Phase::Configure => "configure",
+            Phase::Compile => "compile",
+            Phase::Install => "install",
+        }
+    }
+
+    /// The command the manifest declares for this phase.
+    fn command(self, build: &Build) -> Option<&str> {
+        match self {
+            Phase::Prepare => build.prepare.as_deref(),
+            Phase::Configure => build.configure.as_deref(),
+            Phase::Compile => build.compile.as_deref(),
+            Phase::Install => build.install.as_deref(),
+        }
+    }
+}
+
+/// Run the build recipe of a package, falling back to what the source tree looks like for
+/// anything the manifest doesn't declare.
+///
+/// Returns the arguments the build options were passed as.
+pub(crate) async fn run(
+    config: &mix_config::Config,
+    context: &Context<'_>,
+    build: &Build,
+) -> Result<Vec<String>> {
+    let shell = config.shell();
+    let detected = configs::System::new(context.package_id, context.source_dir).await;
+
+    for (_name, path) in detected.config.iter() {
+        header!(shell, "{} {}", shell.theme().command_paint("found"), path)?;
+    }
+
+    let system = build.system.or_else(|| detect(&detected));
+    let undeclared = [Phase::Compile, Phase::Install]
+        .into_iter()
+        .any(|phase| phase.command(build).is_none());
+
+    if system.is_none() && undeclared {
+        return Err(format!(
+            "unable to detect the build system of {}, declare `build.system` in its manifest",
+            context.package_id
+        )
+        .into());
+    }
+
+    // custom configure commands see the options in their environment
+    let custom = system.is_none() || Phase::Configure.command(build).is_some();
+    let option_args = context.options.args(system, custom)?;
+
+    // cmake and meson refuse to configure in the source directory, and make builds where its
+    // makefile is unless the manifest says otherwise.
+    let separate = match system {
+        Some(System::Cmake | System::Meson) => build.build_dir.unwrap_or(true),
+        Some(System::Make) => build.build_dir.unwrap_or(false),
+        _ => build.build_dir.unwrap_or(context.build_dir),
+    };
+
+    let work_dir = if separate {
+        context.extract_dir.join("build")
+    } else {
+        context.source_dir.to_path_buf()
+    };
+
+    let _ = work_dir.create_dir_all_async().await;
+
+    if let Some(system) = system {
+        header!(
+            shell,
+            "{} {}",
+            shell.theme().command_paint("system"),
+            system
+        )?;
+    }
+
+    header!(
+        shell,
+        "{} {}",
+        shell.theme().command_paint("build"),
+        &work_dir
+    )?;
+
+    for arg in option_args.iter() {
+        header!(shell, "{} {}", shell.theme().command_paint("option"), arg)?;
+    }
+
+    for phase in [
+        Phase::Prepare,
+        Phase::Configure,
+        Phase::Compile,
+        Phase::Install,
+    ] {
+        let commands = match phase.command(build) {
+            Some(script) if script.trim().is_empty() => Vec::new(),
+            Some(script) => {
+                let mut command = Command::sh();
+
+                command
+                    .arg("-c")
+                    .arg(script)
+                    .env("PREFIX", context.destination)
+                    .env("JOBS", context.jobs.to_string())
+                    .env("TARGET", context.target.as_str())
+                    .env("SOURCE_DIR", context.source_dir)
+                    .env("BUILD_DIR", &work_dir)
+                    .envs(context.options.env());
+
+                vec![(command, work_dir.clone())]
+            }
+            None => match system {
+                Some(system) => {
+                    let args = build.args.iter().chain(option_args.iter());
+
+                    default_commands(context, &detected, system, phase, &work_dir, args).await
+                }
+                None => Vec::new(),
+            },
+        };
+
+        for (mut command, dir) in commands {
+            command
+                .current_dir(&dir)
+                .home_dir(context.extract_dir)
+                .lang(EN_US)
+                .envs(build.env.iter())
+                .stderr(Stdio::piped())
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped());
+
+            execute(config, phase.name(), &mut command).await?;
+        }
+    }
+
+    Ok(option_args)
+}
+
+/// Guess the build system from the files in the source directory.
+fn detect(detected: &configs::System) -> Option<System> {
+    if detected.has_autotools() {
+        Some(System::Autotools)
+    } else if detected.has_cmake() {
+        Some(System::Cmake)
+    } else if detected.has_meson() {
+        Some(System::Meson)
+    } else if detected.has_cargo() {
+        Some(System::Cargo)
+    } else if detected.has_makefile() {
+        Some(System::Make)
+    } else {
+        None
+    }
+}
+
+/// `make`, reading the makefile from the source directory when building outside of it.
+fn make(detected: &configs::System, source_dir: &Path, work_dir: &Path) -> Command {
+    let mut command = Command::make();
+
+    if work_dir.as_str() != source_dir.as_str() {
+        if let Some(makefile) = detected.get_makefile() {
+            command
+                .arg("-f")
+                .arg(makefile)
+                .arg(format!("VPATH={}", source_dir));
+        }
+    }
+
+    command
+}
+
+/// What `system` runs for `phase`, and where, with `args` from the manifest and build options.
+async fn default_commands<'a>(
+    context: &Context<'_>,
+    detected: &configs::System,
+    system: System,
+    phase: Phase,
+    work_dir: &Path,
+    args: impl Iterator<Item = &'a String> + Clone,
+) -> Vec<(Command, PathBuf)> {
+    let source_dir = context.source_dir;
+    let destination = context.destination;
+    let jobs = format!("-j{}", context.jobs);
+    let configure = source_dir.join("configure");
+
+    match (system, phase) {
+        (System::Autotools, Phase::Prepare) => {
+            // Release tarballs ship a generated configure script. Generators only work from the
+            // source directory.
+            if configure.exists_async().await {
+                return Vec::new();
+            }
+
+            if let Some(bootstrap) = detected.get_bootstrap() {
+                let mut command = Command::new(bootstrap);
+
+                command
+                    .c_compiler(CLANG)
+                    .c_flags(context.cflags)
+                    .cxx_compiler(CLANGXX)
+                    .cxx_flags(context.cflags)
+                    .linker(LLD)
+                    .linker_flags(context.ldflags);
+
+                vec![(command, source_dir.to_path_buf())]
+            } else if let Some(autogen) = detected.get_autogen() {
+                vec![(Command::new(autogen), source_dir.to_path_buf())]
+            } else if detected.has_configure_ac() {
+                let mut command = Command::autoreconf();
+
+                command.arg("-f").arg("-i");
+
+                vec![(command, source_dir.to_path_buf())]
+            } else {
+                Vec::new()
+            }
+        }
+        (System::Autotools, Phase::Configure) => {
+            // Some projects only ship a makefile once generated.
+            if !configure.exists_async().await {
+                return Vec::new();
+            }
+
+            let mut command = Command::new(&configure);
+
+            command
+                .arg(format!("--prefix={}", destination))
+                .arg(format!("--target={}", context.target.as_str()))
+                .args(args.clone());
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Autotools, Phase::Compile) => {
+            let mut command = Command::make();
+
+            command.arg(&jobs);
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Autotools, Phase::Install) => {
+            let mut command = Command::make();
+
+            command.arg("install").arg(&jobs);
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Cmake, Phase::Prepare) => Vec::new(),
+        (System::Cmake, Phase::Configure) => {
+            let mut command = Command::cmake();
+
+            command
+                .arg("-S")
+                .arg(source_dir)
+                .arg("-B")
+                .arg(work_dir)
+                .arg(format!("-DCMAKE_INSTALL_PREFIX={}", destination))
+                .args(args.clone());
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Cmake, Phase::Compile) => {
+            let mut command = Command::cmake();
+
+            command.arg("--build").arg(work_dir).arg(&jobs);
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Cmake, Phase::Install) => {
+            let mut command = Command::cmake();
+
+            command.arg("--install").arg(work_dir);
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Meson, Phase::Prepare) => Vec::new(),
+        (System::Meson, Phase::Configure) => {
+            let mut command = Command::meson();
+
+            command
+                .arg("setup")
+                .arg(format!("--prefix={}", destination))
+                .args(args.clone())
+                .arg(work_dir)
+                .arg(source_dir);
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Meson, Phase::Compile) => {
+            let mut command = Command::meson();
+
+            command.arg("compile").arg("-C").arg(work_dir).arg(&jobs);
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Meson, Phase::Install) => {
+            let mut command = Command::meson();
+
+            command.arg("install").arg("-C").arg(work_dir);
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Make, Phase::Prepare | Phase::Configure) => Vec::new(),
+        (System::Make, Phase::Compile) => {
+            let mut command = make(detected, source_dir, work_dir);
+
+            command
+                .arg(&jobs)
+                .arg(format!("PREFIX={}", destination))
+                .args(args.clone());
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Make, Phase::Install) => {
+            let mut command = make(detected, source_dir, work_dir);
+
+            command
+                .arg("install")
+                .arg(&jobs)
+                .arg(format!("PREFIX={}", destination))
+                .args(args.clone());
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+        (System::Cargo, Phase::Prepare | Phase::Configure | Phase::Compile) => Vec::new(),
+        (System::Cargo, Phase::Install) => {
+            let mut command = Command::cargo();
+
+            command
+                .arg("install")
+                .arg(&jobs)
+                .arg("--path")
+                .arg(source_dir)
+                .arg("--root")
+                .arg(destination)
+                .arg("--target-dir")
+                .arg(work_dir)
+                .args(args.clone());
+
+            vec![(command, work_dir.to_path_buf())]
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use crate::{Result, Value};
+use path::Path;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// What went into an install, written into the install directory.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Record {
+    /// Patches applied to the sources, in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub patches: Vec<Applied>,
+
+    /// Values of the build options the package declares.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub options: BTreeMap<String, Value>,
+
+    /// Arguments the build options were passed to the build system as.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+
+    /// Checksum of the source tarball.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+/// A patch applied to the sources of an install.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Applied {
+    pub file: String,
+    pub sha256: String,
+}
+
+impl Record {
+    pub const FILE_NAME: &'static str = ".mix.yml";
+
+    /// Read the record of the install at `install_dir`, if it has one.
+    pub async fn load(install_dir: impl AsRef<Path>) -> Result<Option<Self>> {
+        let path = install_dir.as_ref().join(Self::FILE_NAME);
+
+        if !path.exists_async().await {
+            return Ok(None);
+        }
+
+        let text = path.read_to_string_async().await?;
+
+        Ok(Some(serde_yaml::from_str(text.as_str())?))
+    }
+
+    /// Write this record into the install at `install_dir`.
+    pub async fn save(&self, install_dir: impl AsRef<Path>) -> Result<()> {
+        let text = serde_yaml::to_string(self)?;
+
+        install_dir
+            .as_ref()
+            .join(Self::FILE_NAME)
+            .write_async(text.as_str())
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1041 @@
+use crate::Result;
+use mix_atom::Requirement;
+use mix_packages::{Package, Packages};
+use mix_shell::{header, write, writeln, AsyncDisplay, AsyncWrite};
+use mix_source::versions::Entry;
+use mix_version::Version;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// The version picked for a package.
+#[derive(Clone, Debug)]
+pub enum Selection {
+    /// An installed version satisfies every constraint, nothing to build.
+    Installed(Version),
+
+    /// An upstream release which has to be built.
+    Build(Entry),
+}
+
+impl Selection {
+    pub fn version(&self) -> &Version {
+        match self {
+            Selection::Installed(version) => version,
+            Selection::Build(entry) => &entry.version,
+        }
+    }
+}
+
+/// A package and the version picked for it.
+#[derive(Clone, Debug)]
+pub struct Step {
+    pub package: Package,
+    pub selection: Selection,
+}
+
+/// When a dependency is needed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Kind {
+    /// While building, and for as long as the dependent stays installed.
+    Runtime,
+
+    /// Only while building the dependent.
+    Build,
+}
+
+/// A requirement, and what it was required by.
+struct Constraint<'a> {
+    /// `None` for requirements given on the command line, or pinned.
+    dependent: Option<&'a Package>,
+    requirement: &'a Requirement,
+
+    /// Whether the requirement is a pin, which constrains without requesting.
+    pinned: bool,
+}
+
+#[derive(Default)]
+struct Graph<'a> {
+    /// Packages requested directly.
+    roots: Vec<&'a Package>,
+
+    /// Requirements placed on the packages they name, once something else requires them.
+    pins: &'a [Requirement],
+
+    /// Resolved dependencies of each package.
+    edges: HashMap<&'a Package, Vec<(Kind, &'a Package)>>,
+
+    /// Every constraint placed on a package.
+    constraints: HashMap<&'a Package, Vec<Constraint<'a>>>,
+
+    /// Requirements no package matches.
+    missing: Vec<Constraint<'a>>,
+
+    /// Requirements several packages match with the same priority.
+    ambiguous: Vec<(Constraint<'a>, Vec<&'a Package>)>,
+
+    /// Packages picked over others by repository priority.
+    shadowed: Vec<(&'a Package, Vec<&'a Package>)>,
+
+    /// Requirements which must already be installed.
+    bootstrap: Vec<Constraint<'a>>,
+}
+
+/// Upstream versions of a package, and whether its tags were fetched for them.
+type Upstream = (bool, mix_source::versions::Versions);
+
+/// Packages in build order, and the dependency cycles which prevent one.
+#[derive(Default)]
+struct Order<'a> {
+    /// Packages, each after all of its dependencies.
+    packages: Vec<&'a Package>,
+
+    /// Packages currently being visited, outermost first.
+    stack: Vec<&'a Package>,
+
+    /// Dependency cycles, each starting and ending with the same package.
+    cycles: Vec<Vec<&'a Package>>,
+}
+
+/// Pick a version for `requirements` and all of their dependencies.
+///
+/// The newest version satisfying every constraint placed on a package, and its hold in
+/// `settings.yml`, is picked, whether it's installed or upstream. Conflicts are explained through
+/// the shell before an error is returned.
+///
+/// Dependency cycles are conflicts too, unless one edge is declared as `bootstrap` in the
+/// manifest, which requires an installed version instead of ordering the build.
+///
+/// Build dependencies are only resolved for packages which are built. Test dependencies are not
+/// resolved, as nothing runs test suites yet.
+///
+/// `pins`, such as the entries of a lockfile, narrow the versions of the packages they name
+/// without requesting them, so packages nothing requires stay out of the steps.
+pub async fn solve(
+    config: &mix_config::Config,
+    packages: &Packages,
+    requirements: &[Requirement],
+    pins: &[Requirement],
+) -> Result<Vec<Step>> {
+    let mut graph = Graph {
+        pins,
+        ..Graph::default()
+    };
+
+    for requirement in requirements {
+        if let Some(package) = visit(config, packages, None, requirement, &mut graph) {
+            graph.roots.push(package);
+        }
+    }
+
+    // Whether a package is built depends on every constraint placed on it, so versions are
+    // picked again once the build dependencies of packages being built are resolved.
+    let mut upstreams = HashMap::new();
+    let mut expanded = HashSet::new();
+
+    let selections = loop {
+        let selections = select(config, &graph, &mut upstreams).await?;
+        let building = selections
+            .iter()
+            .filter(|(_package, selection)| matches!(selection, Some(Selection::Build(_))))
+            .map(|(package, _selection)| *package)
+            .filter(|package| expanded.insert(*package))
+            .collect::<Vec<_>>();
+
+        if building.is_empty() {
+            break selections;
+        }
+
+        for package in building {
+            for requirement in package.build_dependencies() {
+                if let Some(dependency) =
+                    visit(config, packages, Some(package), requirement, &mut graph)
+                {
+                    graph
+                        .edges
+                        .entry(package)
+                        .or_default()
+                        .push((Kind::Build, dependency));
+                }
+            }
+        }
+    };
+
+    let order = order(&graph, &selections);
+    let needed = order.packages.iter().copied().collect::<HashSet<_>>();
+
+    // Requirements of packages which turned out not to be needed don't matter.
+    let relevant = |constraint: &Constraint<'_>| {
+        constraint
+            .dependent
+            .map_or(true, |dependent| needed.contains(dependent))
+    };
+
+    let shell = config.shell();
+    let mut conflicts = 0;
+
+    for (package, shadowed) in graph.shadowed.iter() {
+        if !needed.contains(package) {
+            continue;
+        }
+
+        header!(
+            shell,
+            "{} {}/{} shadows {}",
+            shell.theme().warning_paint("shadowed"),
+            package.repository_id(),
+            package.package_id(),
+            candidates(shadowed)
+        )?;
+    }
+
+    for constraint in graph
+        .missing
+        .iter()
+        .filter(|constraint| relevant(*constraint))
+    {
+        header!(
+            shell,
+            "{} {} {}",
+            shell.theme().error_paint("missing"),
+            shell.theme().arguments_paint(constraint.requirement),
+            required_by(constraint)
+        )?;
+
+        conflicts += 1;
+    }
+
+    for (constraint, matches) in graph.ambiguous.iter() {
+        if !relevant(constraint) {
+            continue;
+        }
+
+        header!(
+            shell,
+            "{} {} {}, candidates: {} (set priority in settings.yml)",
+            shell.theme().error_paint("ambiguous"),
+            shell.theme().arguments_paint(constraint.requirement),
+            required_by(constraint),
+            candidates(matches)
+        )?;
+
+        conflicts += 1;
+    }
+
+    for cycle in order.cycles.iter() {
+        write!(
+            shell,
+            "{}{} ",
+            shell.theme().header_prefix(),
+            shell.theme().error_paint("cycle")
+        )?;
+
+        for (index, package) in cycle.iter().enumerate() {
+            if index > 0 {
+                write!(shell, " {} ", shell.theme().seperator_paint("->"))?;
+            }
+
+            AsyncDisplay::fmt(*package, shell).await?;
+        }
+
+        writeln!(shell)?;
+        conflicts += 1;
+    }
+
+    if !order.cycles.is_empty() {
+        header!(
+            shell,
+            "{}",
+            "move one dependency of each cycle to bootstrap to break it"
+        )?;
+    }
+
+    for constraint in graph
+        .bootstrap
+        .iter()
+        .filter(|constraint| relevant(*constraint))
+    {
+        let matches = packages.matches(constraint.requirement).collect::<Vec<_>>();
+        let installed = crate::prefer(config, &matches).map_or(false, |(package, _shadowed)| {
+            package
+                .versions()
+                .matches(&constraint.requirement.requirement)
+                .next()
+                .is_some()
+        });
+
+        if !installed {
+            header!(
+                shell,
+                "{} {} {}, but isn't installed",
+                shell.theme().error_paint("bootstrap"),
+                shell.theme().arguments_paint(constraint.requirement),
+                required_by(constraint)
+            )?;
+
+            conflicts += 1;
+        }
+    }
+
+    let mut steps = Vec::new();
+
+    for package in order.packages {
+        match &selections[package] {
+            Some(selection) => steps.push(Step {
+                package: Package::clone(package),
+                selection: selection.clone(),
+            }),
+            None => {
+                let hold = config.hold(package.repository_id(), package.package_id());
+                let (_fetched, upstream) = &upstreams[package];
+                let versions = package
+                    .versions()
+                    .iter()
+                    .chain(upstream.iter().map(|entry| &entry.version))
+                    .collect::<BTreeSet<_>>();
+
+                explain(
+                    config,
+                    package,
+                    &graph.constraints[package],
+                    hold,
+                    &versions,
+                )
+                .await?;
+                conflicts += 1;
+            }
+        }
+    }
+
+    if conflicts > 0 {
+        return Err(format!("unable to resolve dependencies, {} conflict(s)", conflicts).into());
+    }
+
+    check_conflicts(config, packages, &steps).await?;
+    check_replaced(config, packages, &steps, &replaced(packages, &steps)).await?;
+
+    Ok(steps)
+}
+
+/// Pick the newest allowed version of every package in `graph`, `None` where there's none.
+async fn select<'a>(
+    config: &mix_config::Config,
+    graph: &Graph<'a>,
+    upstreams: &mut HashMap<&'a Package, Upstream>,
+) -> Result<HashMap<&'a Package, Option<Selection>>> {
+    let mut selections = HashMap::new();
+
+    for (package, constraints) in graph.constraints.iter() {
+        let hold = config.hold(package.repository_id(), package.package_id());
+        let allowed = |version: &Version| {
+            hold.map_or(true, |hold| version.matches(hold))
+                && constraints
+                    .iter()
+                    .all(|constraint| version.matches(&constraint.requirement.requirement))
+        };
+
+        let installed = package
+            .versions()
+            .iter()
+            .filter(|version| allowed(version))
+            .last();
+
+        // Where an installed version will do, newer versions are only picked from cached tags,
+        // so resolving installed packages works offline.
+        let fetch = installed.is_none();
+        let stale = upstreams
+            .get(package)
+            .map_or(true, |(fetched, _upstream)| fetch && !fetched);
+
+        if stale {
+            if fetch {
+                for source in package.sources().iter() {
+                    source.update(config).await?;
+                }
+            }
+
+            upstreams.insert(*package, (fetch, package.upstream_versions(config).await));
+        }
+
+        let (_fetched, upstream) = &upstreams[package];
+        let latest = upstream
+            .iter()
+            .filter(|entry| allowed(&entry.version))
+            .last();
+
+        let selection = match (installed, latest) {
+            (Some(installed), Some(entry)) if &entry.version > installed => {
+                Some(Selection::Build(entry.clone()))
+            }
+            (Some(installed), _) => Some(Selection::Installed(installed.clone())),
+            (None, Some(entry)) => Some(Selection::Build(entry.clone())),
+            (None, None) => None,
+        };
+
+        selections.insert(*package, selection);
+    }
+
+    Ok(selections)
+}
+
+/// Order the packages reachable from the roots of `graph`, dependencies first.
+///
+/// Build dependencies are only followed for packages which are built.
+fn order<'a>(graph: &Graph<'a>, selections: &HashMap<&'a Package, Option<Selection>>) -> Order<'a> {
+    let mut order = Order::default();
+    let mut exists = HashSet::new();
+
+    for root in graph.roots.iter() {
+        walk(graph, selections, root, &mut order, &mut exists);
+    }
+
+    order
+}
+
+fn walk<'a>(
+    graph: &Graph<'a>,
+    selections: &HashMap<&'a Package, Option<Selection>>,
+    package: &'a Package,
+    order: &mut Order<'a>,
+    exists: &mut HashSet<&'a Package>,
+) {
+    if let Some(index) = order.stack.iter().position(|visiting| *visiting == package) {
+        let mut cycle = order.stack[index..].to_vec();
+
+        cycle.push(package);
+        order.cycles.push(cycle);
+
+        return;
+    }
+
+    if !exists.insert(package) {
+        return;
+    }
+
+    let building = matches!(selections.get(package), Some(Some(Selection::Build(_))));
+
+    order.stack.push(package);
+
+    for (kind, dependency) in graph.edges.get(package).into_iter().flatten() {
+        if building || *kind == Kind::Runtime {
+            walk(graph, selections, dependency, order, exists);
+        }
+    }
+
+    order.stack.pop();
+    order.packages.push(package);
+}
+
+/// Installed versions which a package built by `steps` replaces.
+///
+/// `replaces` names concrete packages, never what they provide, and nothing in `steps` is
+/// replaced.
+pub(crate) fn replaced<'a>(
+    packages: &'a Packages,
+    steps: &[Step],
+) -> Vec<(&'a Package, Vec<&'a Version>)> {
+    packages
+        .installed()
+        .iter()
+        .filter(|package| !steps.iter().any(|step| is_same(&step.package, package)))
+        .filter_map(|package| {
+            let versions = package
+                .versions()
+                .iter()
+                .filter(|version| {
+                    replacements(steps).any(|(_replacer, requirement)| {
+                        names(package, requirement) && version.matches(&requirement.requirement)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            (!versions.is_empty()).then(|| (package, versions))
+        })
+        .collect()
+}
+
+/// Refuse to replace versions which installed packages still depend on, unless what `steps`
+/// installs satisfies them instead.
+async fn check_replaced(
+    config: &mix_config::Config,
+    packages: &Packages,
+    steps: &[Step],
+    replaced: &[(&Package, Vec<&Version>)],
+) -> Result<()> {
+    let shell = config.shell();
+    let mut broken = 0;
+
+    for (package, versions) in replaced.iter() {
+        let remaining = package
+            .versions()
+            .iter()
+            .filter(|version| !versions.contains(version))
+            .collect::<Vec<_>>();
+
+        let dependents = packages.dependents(package).filter(|dependent| {
+            !replaced
+                .iter()
+                .any(|(replaced, _versions)| is_same(replaced, dependent))
+        });
+
+        for dependent in dependents {
+            let requirements = dependent
+                .dependencies()
+                .iter()
+                .filter(|requirement| package.is_required_by(requirement));
+
+            for requirement in requirements {
+                let satisfied = remaining
+                    .iter()
+                    .any(|version| version.matches(&requirement.requirement))
+                    || steps.iter().any(|step| {
+                        step.package.is_required_by(requirement)
+                            && (!names(&step.package, requirement)
+                                || step.selection.version().matches(&requirement.requirement))
+                    });
+
+                if satisfied {
+                    continue;
+                }
+
+                let (replacer, _requirement) = replacements(steps)
+                    .find(|(_replacer, replacing)| names(package, replacing))
+                    .expect("replaced by some step");
+
+                header!(
+                    shell,
+                    "{} {}/{} requires {}, which {}/{} replaces",
+                    shell.theme().error_paint("required"),
+                    dependent.repository_id(),
+                    dependent.package_id(),
+                    shell.theme().arguments_paint(requirement),
+                    replacer.repository_id(),
+                    replacer.package_id(),
+                )?;
+
+                broken += 1;
+            }
+        }
+    }
+
+    if broken > 0 {
+        return Err(format!(
+            "{} installed package(s) still depend on what would be replaced",
+            broken
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+/// The `replaces` entries of every package built by `steps`.
+fn replacements(steps: &[Step]) -> impl Iterator<Item = (&Package, &Requirement)> {
+    steps
+        .iter()
+        .filter(|step| matches!(step.selection, Selection::Build(_)))
+        .flat_map(|step| {
+            step.package
+                .replaces()
+                .iter()
+                .map(move |requirement| (&step.package, requirement))
+        })
+}
+
+/// Whether `requirement` names `package` itself, rather than something it provides.
+fn names(package: &Package, requirement: &Requirement) -> bool {
+    requirement.package_id == *package.package_id()
+        && requirement
+            .repository_id
+            .as_ref()
+            .map_or(true, |repository_id| {
+                repository_id == package.repository_id()
+            })
+}
+
+fn is_same(package: &Package, other: &Package) -> bool {
+    package.repository_id() == other.repository_id() && package.package_id() == other.package_id()
+}
+
+/// Report packages which declare a conflict with each other, and would both end up installed.
+///
+/// Conflicts between packages which are already installed are left alone.
+async fn check_conflicts(
+    config: &mix_config::Config,
+    packages: &Packages,
+    steps: &[Step],
+) -> Result<()> {
+    let mut installs = steps
+        .iter()
+        .map(|step| {
+            let versions = step
+                .package
+                .versions()
+                .iter()
+                .chain(Some(step.selection.version()))
+                .collect::<Vec<_>>();
+
+            (&step.package, versions, true)
+        })
+        .collect::<Vec<_>>();
+
+    let replaced = replaced(packages, steps);
+
+    for package in packages.installed().iter() {
+        if steps.iter().any(|step| is_same(&step.package, package)) {
+            continue;
+        }
+
+        let versions = package
+            .versions()
+            .iter()
+            .filter(|version| {
+                !replaced.iter().any(|(replaced, versions)| {
+                    is_same(replaced, package) && versions.contains(version)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if !versions.is_empty() {
+            installs.push((package, versions, false));
+        }
+    }
+
+    let shell = config.shell();
+    let mut conflicts = 0;
+
+    for (package, _versions, new) in installs.iter() {
+        for (other, versions, other_new) in installs.iter() {
+            if package == other || !(*new || *other_new) {
+                continue;
+            }
+
+            for requirement in package.conflicts() {
+                let conflicting = other.is_required_by(requirement)
+                    && versions
+                        .iter()
+                        .any(|version| version.matches(&requirement.requirement));
+
+                if !conflicting {
+                    continue;
+                }
+
+                header!(
+                    shell,
+                    "{} {}/{} conflicts with {}/{} ({})",
+                    shell.theme().error_paint("conflict"),
+                    package.repository_id(),
+                    package.package_id(),
+                    other.repository_id(),
+                    other.package_id(),
+                    shell.theme().arguments_paint(requirement)
+                )?;
+
+                conflicts += 1;
+            }
+        }
+    }
+
+    if conflicts > 0 {
+        return Err(format!("{} package conflict(s)", conflicts).into());
+    }
+
+    Ok(())
+}
+
+fn visit<'a>(
+    config: &mix_config::Config,
+    packages: &'a Packages,
+    dependent: Option<&'a Package>,
+    requirement: &'a Requirement,
+    graph: &mut Graph<'a>,
+) -> Option<&'a Package> {
+    let constraint = Constraint {
+        dependent,
+        requirement,
+        pinned: false,
+    };
+
+    let matches = packages.matches(requirement).collect::<Vec<_>>();
+
+    if matches.is_empty() {
+        graph.missing.push(constraint);
+
+        return None;
+    }
+
+    let (package, shadowed) = match crate::prefer(config, &matches) {
+        Some(preferred) => preferred,
+        None => {
+            graph.ambiguous.push((constraint, matches));
+
+            return None;
+        }
+    };
+
+    let visited = graph.constraints.contains_key(package);
+
+    graph
+        .constraints
+        .entry(package)
+        .or_default()
+        .push(constraint);
+
+    if visited {
+        return Some(package);
+    }
+
+    if !shadowed.is_empty() {
+        graph.shadowed.push((package, shadowed));
+    }
+
+    let pins = graph.pins;
+
+    for requirement in pins.iter().filter(|pin| names(package, pin)) {
+        graph
+            .constraints
+            .entry(package)
+            .or_default()
+            .push(Constraint {
+                dependent: None,
+                requirement,
+                pinned: true,
+            });
+    }
+
+    for requirement in package.bootstrap() {
+        graph.bootstrap.push(Constraint {
+            dependent: Some(package),
+            requirement,
+            pinned: false,
+        });
+    }
+
+    // Build dependencies are resolved once it's known whether the package is built.
+    for requirement in package.dependencies() {
+        if let Some(dependency) = visit(config, packages, Some(package), requirement, graph) {
+            graph
+                .edges
+                .entry(package)
+                .or_default()
+                .push((Kind::Runtime, dependency));
+        }
+    }
+
+    Some(package)
+}
+
+fn required_by(constraint: &Constraint<'_>) -> String {
+    match constraint.dependent {
+        Some(package) => format!(
+            "required by {}/{}",
+            package.repository_id(),
+            package.package_id()
+        ),
+        None if constraint.pinned => "pinned".to_string(),
+        None => "requested".to_string(),
+    }
+}
+
+fn candidates(packages: &[&Package]) -> String {
+    packages
+        .iter()
+        .map(|package| format!("{}/{}", package.repository_id(), package.package_id()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Show each constraint on `package`, alongside the versions it allows.
+async fn explain(
+    config: &mix_config::Config,
+    package: &Package,
+    constraints: &[Constraint<'_>],
+    hold: Option<&mix_version::Requirement>,
+    versions: &BTreeSet<&Version>,
+) -> Result<()> {
+    let shell = config.shell();
+
+    header!(
+        shell,
+        "{} no version of {}/{} satisfies every requirement",
+        shell.theme().error_paint("conflict"),
+        package.repository_id(),
+        package.package_id()
+    )?;
+
+    for constraint in constraints {
+        header!(
+            shell,
+            "  {} {}, allows {}",
+            shell.theme().arguments_paint(constraint.requirement),
+            required_by(constraint),
+            allows(versions, &constraint.requirement.requirement)
+        )?;
+    }
+
+    if let Some(hold) = hold {
+        header!(
+            shell,
+            "  {}/{}:{} held in settings.yml, allows {}",
+            package.repository_id(),
+            package.package_id(),
+            hold,
+            allows(versions, hold)
+        )?;
+    }
+
+    if versions.is_empty() {
+        header!(shell, "  {}", "no versions are installed or known upstream")?;
+    }
+
+    Ok(())
+}
+
+/// The versions in `versions` which `requirement` allows, for display.
+fn allows(versions: &BTreeSet<&Version>, requirement: &mix_version::Requirement) -> String {
+    let allowed = versions
+        .iter()
+        .filter(|version| version.matches(requirement))
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+
+    if allowed.is_empty() {
+        "nothing".to_string()
+    } else {
+        allowed.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_conflicts, replaced, solve, Selection, Step};
+    use mix_atom::Requirement;
+    use mix_id::{PackageId, RepositoryId};
+    use mix_manifest::Build;
+    use mix_packages::{Package, PackageRef, Packages};
+    use mix_source::versions::Entry;
+    use mix_source::{Source, Sources};
+    use mix_version::{Version, Versions};
+    use path::Path;
+    use std::collections::{BTreeMap, BTreeSet};
+    use url::Url;
+
+    /// `repository/package`, installed at `versions`, without any sources.
+    fn package(id: &str, versions: &[&str]) -> PackageRef {
+        let (repository_id, package_id) = id.split_once('/').unwrap();
+        let mut installed = Versions::new();
+
+        for version in versions {
+            installed.insert(
+                Version::parse(version).unwrap(),
+                Path::new("/prefix").join(id).join(version),
+            );
+        }
+
+        PackageRef {
+            repository_id: RepositoryId::try_from(repository_id).unwrap(),
+            package_id: PackageId::try_from(package_id).unwrap(),
+            versions: installed,
+            description: None,
+            homepage: None,
+            license: None,
+            maintainers: Vec::new(),
+            keywords: BTreeSet::new(),
+            dependencies: BTreeSet::new(),
+            build_dependencies: BTreeSet::new(),
+            test_dependencies: BTreeSet::new(),
+            bootstrap: BTreeSet::new(),
+            sources: Sources::new("/cache"),
+            provides: BTreeSet::new(),
+            conflicts: BTreeSet::new(),
+            replaces: BTreeSet::new(),
+            targets: BTreeMap::new(),
+            build: Build::default(),
+            options: BTreeMap::new(),
+            patches: None,
+            checksums: Vec::new(),
+            manifest_path: Path::new("/repos").join(id).join("manifest.yml"),
+            patches_dir: Path::new("/repos").join(id).join("patches"),
+            build_prefix: Path::new("/build").join(id),
+        }
+    }
+
+    fn requirements(requirements: &[&str]) -> BTreeSet<Requirement> {
+        requirements
+            .iter()
+            .map(|requirement| Requirement::parse(requirement).unwrap())
+            .collect()
+    }
+
+    fn packages(list: Vec<PackageRef>) -> Packages {
+        let mut packages = Packages::new();
+
+        for package in list {
+            packages.insert(package);
+        }
+
+        packages
+    }
+
+    /// A build of `package` at `version`.
+    fn build(package: PackageRef, version: &str) -> Step {
+        let url = format!(
+            "https://example.com/{}-{}.tar.gz",
+            package.package_id.as_str(),
+            version
+        );
+
+        Step {
+            package: Package::from(package),
+            selection: Selection::Build(Entry {
+                path: Path::new("/cache").join(version),
+                url: Url::parse(&url).unwrap(),
+                version: Version::parse(version).unwrap(),
+                source: Source::github("example", "example"),
+            }),
+        }
+    }
+
+    /// A config with its prefix in a fresh temporary directory, and `settings` as `settings.yml`.
+    async fn config(name: &str, settings: &str) -> mix_config::Config {
+        let prefix = Path::new(std::env::temp_dir().to_str().unwrap()).join(format!(
+            "mix-solve-{}-{}",
+            name,
+            std::process::id()
+        ));
+
+        let _ = tokio::fs::remove_dir_all(&prefix).await;
+
+        prefix.create_dir_all_async().await.unwrap();
+        prefix
+            .join("settings.yml")
+            .write_async(settings)
+            .await
+            .unwrap();
+
+        mix_config::Config::new(&prefix).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn cycle_is_a_conflict() {
+        let config = config("cycle", "").await;
+        let mut a = package("core/a", &["1.0.0"]);
+        let mut b = package("core/b", &["1.0.0"]);
+
+        a.dependencies = requirements(&["b"]);
+        b.dependencies = requirements(&["a"]);
+
+        let packages = packages(vec![a, b]);
+        let result = solve(&config, &packages, &[Requirement::parse("a").unwrap()], &[]).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn bootstrap_breaks_a_cycle() {
+        let config = config("bootstrap", "").await;
+        let mut a = package("core/a", &["1.0.0"]);
+        let mut b = package("core/b", &["1.0.0"]);
+
+        a.dependencies = requirements(&["b"]);
+        b.bootstrap = requirements(&["a"]);
+
+        let packages = packages(vec![a, b]);
+        let steps = solve(&config, &packages, &[Requirement::parse("a").unwrap()], &[])
+            .await
+            .unwrap();
+
+        assert_eq!(steps.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn conflict_unless_replaced() {
+        let config = config("conflict", "").await;
+        let packages = packages(vec![package("core/b", &["1.0.0"])]);
+
+        let mut conflicting = package("core/a", &[]);
+
+        conflicting.conflicts = requirements(&["b"]);
+
+        let steps = vec![build(conflicting.clone(), "1.0.0")];
+
+        assert!(replaced(&packages, &steps).is_empty());
+        assert!(check_conflicts(&config, &packages, &steps).await.is_err());
+
+        let mut replacing = conflicting;
+
+        replacing.replaces = requirements(&["b"]);
+
+        let steps = vec![build(replacing, "1.0.0")];
+        let replaced = replaced(&packages, &steps);
+
+        assert_eq!(replaced.len(), 1);
+        assert_eq!(replaced[0].0.package_id().as_str(), "b");
+        assert!(check_conflicts(&config, &packages, &steps).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn hold_beats_newer_version() {
+        let config = config("hold", "holds:\n  core/a: \"<2\"\n").await;
+        let packages = packages(vec![package("core/a", &["1.0.0", "2.0.0"])]);
+        let steps = solve(&config, &packages, &[Requirement::parse("a").unwrap()], &[])
+            .await
+            .unwrap();
+
+        assert_eq!(steps.len(), 1);
+        assert!(matches!(
+            &steps[0].selection,
+            Selection::Installed(version) if version == &Version::parse("1.0.0").unwrap()
+        ));
+    }
+
+    #[tokio::test]
+    async fn priority_picks_repository() {
+        let packages = packages(vec![
+            package("core/a", &["1.0.0"]),
+            package("extra/a", &["1.0.0"]),
+        ]);
+
+        let requirement = Requirement::parse("a").unwrap();
+
+        let unprioritized = config("ambiguous", "").await;
+        let prioritized = config("priority", "priority: [extra]\n").await;
+        let ambiguous = solve(&unprioritized, &packages, &[requirement.clone()], &[]).await;
+
+        assert!(ambiguous.is_err());
+
+        let steps = solve(&prioritized, &packages, &[requirement], &[])
+            .await
+            .unwrap();
+
+        assert_eq!(steps.len(), 1);
+        assert_eq!(steps[0].package.repository_id().as_str(), "extra");
+    }
+
+    #[tokio::test]
+    async fn pins_constrain_without_requesting() {
+        let config = config("pins", "").await;
+        let packages = packages(vec![
+            package("core/a", &["1.0.0", "2.0.0"]),
+            package("core/b", &["1.0.0"]),
+        ]);
+
+        let pins = [
+            Requirement::parse("core/a:=1.0.0").unwrap(),
+            Requirement::parse("core/b:=1.0.0").unwrap(),
+        ];
+
+        let steps = solve(
+            &config,
+            &packages,
+            &[Requirement::parse("a").unwrap()],
+            &pins,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(steps.len(), 1);
+        assert_eq!(
+            steps[0].selection.version(),
+            &Version::parse("1.0.0").unwrap()
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use crate::{resolve_one, Result};
+use mix_atom::Requirement;
+use mix_packages::{Package, Packages};
+use mix_shell::{header, write, writeln, AsyncDisplay, AsyncWrite};
+use mix_triple::Triple;
+use path::PathBuf;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+const BRANCH: &str = "├── ";
+const LAST_BRANCH: &str = "└── ";
+const INDENT: &str = "│   ";
+const LAST_INDENT: &str = "    ";
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub target: Triple,
+    pub requirement: Requirement,
+}
+
+enum Node<'a> {
+    /// A resolved package, and whether it was already printed.
+    Package(&'a Package, bool),
+
+    /// A requirement that doesn't resolve to exactly one package.
+    Unresolved(&'a Requirement),
+}
+
+struct Line<'a> {
+    indent: String,
+    node: Node<'a>,
+    /// Whether the edge leading here is a build dependency.
+    build: bool,
+}
+
+/// Requirements of `package`, runtime dependencies first, each with whether it's only needed to
+/// build it.
+pub(crate) fn edges(package: &Package) -> impl Iterator<Item = (&Requirement, bool)> {
+    let runtime = package.dependencies();
+    let build = package
+        .build_dependencies()
+        .iter()
+        .filter(move |requirement| !runtime.contains(*requirement));
+
+    runtime
+        .iter()
+        .map(|requirement| (requirement, false))
+        .chain(build.map(|requirement| (requirement, true)))
+}
+
+/// Print the dependency tree of a package.
+pub async fn tree(
+    config: mix_config::Config,
+    tree_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let packages = packages.for_target(tree_config.target);
+    let package = resolve_one(&config, &packages, &tree_config.requirement)
+        .ok_or_else(|| format!("unable to resolve {}", &tree_config.requirement))?;
+
+    let mut lines = vec![Line {
+        indent: String::new(),
+        node: Node::Package(package, false),
+        build: false,
+    }];
+
+    let mut exists = HashSet::new();
+
+    exists.insert(package);
+    walk(&config, &packages, package, "", &mut lines, &mut exists);
+
+    let shell = config.shell();
+
+    for line in lines {
+        write!(
+            shell,
+            "{}{}",
+            shell.theme().header_prefix(),
+            shell.theme().seperator_paint(&line.indent)
+        )?;
+
+        match line.node {
+            Node::Package(package, duplicate) => {
+                AsyncDisplay::fmt(package, shell).await?;
+
+                for version in package.versions().iter() {
+                    write!(shell, " ")?;
+                    AsyncDisplay::fmt(version, shell).await?;
+                }
+
+                if duplicate {
+                    write!(shell, " {}", shell.theme().seperator_paint("(*)"))?;
+                }
+            }
+            Node::Unresolved(requirement) => {
+                write!(
+                    shell,
+                    "{} {}",
+                    shell.theme().arguments_paint(requirement),
+                    shell.theme().error_paint("(unresolved)")
+                )?;
+            }
+        }
+
+        if line.build {
+            write!(shell, " {}", shell.theme().seperator_paint("(build)"))?;
+        }
+
+        writeln!(shell)?;
+    }
+
+    Ok(())
+}
+
+fn walk<'a>(
+    config: &mix_config::Config,
+    packages: &'a Packages,
+    package: &'a Package,
+    indent: &str,
+    lines: &mut Vec<Line<'a>>,
+    exists: &mut HashSet<&'a Package>,
+) {
+    let dependencies = edges(package).collect::<Vec<_>>();
+
+    for (index, &(requirement, build)) in dependencies.iter().enumerate() {
+        let last = index + 1 == dependencies.len();
+        let branch = [indent, if last { LAST_BRANCH } else { BRANCH }].concat();
+        let child_indent = [indent, if last { LAST_INDENT } else { INDENT }].concat();
+
+        match resolve_one(config, packages, requirement) {
+            Some(dependency) if exists.contains(dependency) => lines.push(Line {
+                indent: branch,
+                node: Node::Package(dependency, true),
+                build,
+            }),
+            Some(dependency) => {
+                exists.insert(dependency);
+                lines.push(Line {
+                    indent: branch,
+                    node: Node::Package(dependency, false),
+                    build,
+                });
+
+                walk(config, packages, dependency, &child_indent, lines, exists);
+            }
+            None => lines.push(Line {
+                indent: branch,
+                node: Node::Unresolved(requirement),
+                build,
+            }),
+        }
+    }
+}
+
+/// Print every dependency path from an explicitly installed package down to a package.
+///
+/// Installed packages which no other installed package depends on are treated as explicitly
+/// installed. Where none of them reach the package, as within a dependency cycle, every other
+/// installed package is tried instead. Edges which are build dependencies are marked.
+pub async fn why(
+    config: mix_config::Config,
+    why_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let packages = packages.for_target(why_config.target);
+    let target = resolve_one(&config, &packages, &why_config.requirement)
+        .ok_or_else(|| format!("unable to resolve {}", &why_config.requirement))?;
+
+    let roots = packages
+        .installed()
+        .iter()
+        .filter(|package| packages.dependents(package).next().is_none());
+
+    let mut paths = Vec::new();
+
+    for root in roots {
+        let mut path = vec![(root, false)];
+
+        find_paths(&config, &packages, target, &mut path, &mut paths);
+    }
+
+    if paths.is_empty() {
+        let roots = packages
+            .installed()
+            .iter()
+            .filter(|package| *package != target);
+
+        for root in roots {
+            let mut path = vec![(root, false)];
+
+            find_paths(&config, &packages, target, &mut path, &mut paths);
+        }
+    }
+
+    let shell = config.shell();
+
+    if paths.is_empty() {
+        header!(
+            shell,
+            "{} is not required by any installed package",
+            shell.theme().arguments_paint(&why_config.requirement)
+        )?;
+
+        return Ok(());
+    }
+
+    for path in paths {
+        write!(shell, "{}", shell.theme().header_prefix())?;
+
+        for (index, (package, build)) in path.iter().enumerate() {
+            if index > 0 {
+                let arrow = if *build { "-(build)->" } else { "->" };
+
+                write!(shell, " {} ", shell.theme().seperator_paint(arrow))?;
+            }
+
+            AsyncDisplay::fmt(*package, shell).await?;
+        }
+
+        if path.len() == 1 {
+            write!(shell, " {}", shell.theme().seperator_paint("(explicit)"))?;
+        }
+
+        writeln!(shell)?;
+    }
+
+    Ok(())
+}
+
+/// Depth-first search for `target`, recording every acyclic path that reaches it, each package
+/// with whether it's a build dependency of the one before it.
+fn find_paths<'a>(
+    config: &mix_config::Config,
+    packages: &'a Packages,
+    target: &'a Package,
+    path: &mut Vec<(&'a Package, bool)>,
+    paths: &mut Vec<Vec<(&'a Package, bool)>>,
+) {
+    let (current, _build) = *path.last().expect("path starts with a root");
+
+    if current == target {
+        paths.push(path.clone());
+
+        return;
+    }
+
+    for (requirement, build) in edges(current) {
+        if let Some(dependency) = resolve_one(config, packages, requirement) {
+            if path.iter().any(|(package, _build)| *package == dependency) {
+                continue;
+            }
+
+            path.push((dependency, build));
+            find_paths(config, packages, target, path, paths);
+            path.pop();
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use crate::record::Record;
+use crate::Result;
+use mix_atom::Requirement;
+use mix_manifest::Autotools;
+use mix_packages::{Package, Packages};
+use mix_shell::{header, write, writeln, AsyncDisplay, AsyncWrite};
+use mix_triple::Triple;
+use path::PathBuf;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub target: Triple,
+    pub requirements: Vec<Requirement>,
+    pub jobs: usize,
+    pub remove_old: bool,
+    pub dry_run: bool,
+}
+
+/// Rebuild installed packages whose sources have newer versions upstream.
+///
+/// Held packages are only upgraded as far as their hold in `settings.yml` allows. Build options
+/// carry over from the installed version. Older versions are only removed once nothing installed
+/// depends on them.
+pub async fn upgrade(
+    config: mix_config::Config,
+    upgrade_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let packages = Arc::new(packages.for_target(upgrade_config.target));
+    let mut candidates = Vec::new();
+
+    if upgrade_config.requirements.is_empty() {
+        candidates.extend(packages.installed().iter());
+    }
+
+    for requirement in upgrade_config.requirements.iter() {
+        let matches = packages
+            .matches(requirement)
+            .filter(|package| package.installed())
+            .collect::<Vec<_>>();
+
+        if matches.is_empty() {
+            return Err(format!("{} is not installed", requirement).into());
+        }
+
+        candidates.extend(matches);
+    }
+
+    let mut order = Vec::new();
+    let mut exists = HashSet::new();
+
+    for package in candidates {
+        dependency_order(&packages, package, &mut order, &mut exists);
+    }
+
+    let mut plan = Vec::new();
+
+    for package in order {
+        if !package.installed() {
+            continue;
+        }
+
+        for source in package.sources().iter() {
+            source.refresh(&config).await?;
+        }
+
+        let upstream = package.upstream_versions(&config).await;
+        let hold = config.hold(package.repository_id(), package.package_id());
+        let latest = upstream
+            .iter()
+            .filter(|entry| hold.map_or(true, |hold| entry.version.matches(hold)))
+            .last();
+
+        let installed = match package.versions().latest() {
+            Some(installed) => installed,
+            None => continue,
+        };
+
+        if let (Some(hold), Some(newest)) = (hold, upstream.latest()) {
+            if &newest.version > installed && !newest.version.matches(hold) {
+                header!(
+                    config.shell(),
+                    "{} {}/{}:{}, {} is available",
+                    config.shell().theme().warning_paint("held"),
+                    package.repository_id(),
+                    package.package_id(),
+                    hold,
+                    newest.version
+                )?;
+            }
+        }
+
+        match latest {
+            Some(latest) if &latest.version > installed => {
+                plan.push((package.clone(), installed.clone(), latest.version.clone()));
+            }
+            _ => {}
+        }
+    }
+
+    if plan.is_empty() {
+        header!(config.shell(), "{}", "everything is up to date")?;
+        config.shell().flush().await?;
+
+        return Ok(());
+    }
+
+    for (package, installed, latest) in plan.iter() {
+        write_plan_entry(&config, package, installed, latest).await?;
+    }
+
+    config.shell().flush().await?;
+
+    if upgrade_config.dry_run {
+        return Ok(());
+    }
+
+    let mut packages = packages;
+
+    for (package, installed, latest) in plan {
+        // Keep the build options the installed version was built with, where still declared.
+        let recorded = match package
+            .versions()
+            .pairs()
+            .find(|(version, _path)| *version == &installed)
+        {
+            Some((_version, path)) => Record::load(path)
+                .await?
+                .map(|record| record.options)
+                .unwrap_or_default(),
+            None => Default::default(),
+        };
+
+        // Options passed as `--with` are set like `-I`, the rest like `-D`.
+        let (include, define) = recorded
+            .into_iter()
+            .filter(|(name, _value)| package.options().contains_key(name))
+            .partition(|(name, _value)| {
+                matches!(package.options()[name].autotools, Some(Autotools::With(_)))
+            });
+
+        let build_config = crate::Config {
+            prefix: upgrade_config.prefix.clone(),
+            target: upgrade_config.target,
+            requirement: Requirement {
+                repository_id: Some(package.repository_id().clone()),
+                package_id: package.package_id().clone(),
+                requirement: mix_version::Requirement::parse(&format!("={}", latest))?,
+            },
+            jobs: upgrade_config.jobs,
+            define,
+            include,
+            build_dir: false,
+            lock: None,
+            locked: None,
+        };
+
+        crate::build(config.clone(), build_config, packages.clone()).await?;
+
+        // Pick up what was just installed, so later builds don't rebuild it.
+        packages = Arc::new(
+            Packages::from_config(&config)
+                .await?
+                .for_target(upgrade_config.target),
+        );
+
+        let package_dir = upgrade_config
+            .prefix
+            .join(upgrade_config.target.as_str())
+            .join(package.repository_id().as_str())
+            .join(package.package_id().as_str());
+
+        if !package_dir.join(latest.to_string()).exists_async().await {
+            return Err(format!(
+                "failed to upgrade {}/{} to {}",
+                package.repository_id(),
+                package.package_id(),
+                latest
+            )
+            .into());
+        }
+
+        if !upgrade_config.remove_old {
+            continue;
+        }
+
+        let old = package
+            .versions()
+            .iter()
+            .filter(|version| *version < &latest)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // Dependents of the reloaded package see the new version too.
+        let dependents = packages
+            .get(package.repository_id(), package.package_id())
+            .map(|package| {
+                packages
+                    .broken_dependents(package, &old)
+                    .map(|dependent| {
+                        format!("{}/{}", dependent.repository_id(), dependent.package_id())
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+
+        if !dependents.is_empty() {
+            header!(
+                config.shell(),
+                "{} {}/{}, required by {}",
+                config.shell().theme().warning_paint("kept old versions of"),
+                package.repository_id(),
+                package.package_id(),
+                dependents.join(", ")
+            )?;
+
+            continue;
+        }
+
+        for version in old.iter() {
+            let destination = package_dir.join(version.to_string());
+
+            if !destination.exists_async().await {
+                continue;
+            }
+
+            header!(
+                config.shell(),
+                "removing {}/{}:{}",
+                package.repository_id(),
+                package.package_id(),
+                version,
+            )?;
+
+            tokio::fs::remove_dir_all(&destination).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn write_plan_entry(
+    config: &mix_config::Config,
+    package: &Package,
+    installed: &mix_version::Version,
+    latest: &mix_version::Version,
+) -> Result<()> {
+    let shell = config.shell();
+
+    write!(
+        shell,
+        "{}{} ",
+        shell.theme().header_prefix(),
+        shell.theme().command_paint("upgrade")
+    )?;
+    AsyncDisplay::fmt(package, shell).await?;
+    write!(shell, " ")?;
+    AsyncDisplay::fmt(installed, shell).await?;
+    write!(shell, " {} ", shell.theme().seperator_paint("->"))?;
+    AsyncDisplay::fmt(latest, shell).await?;
+    writeln!(shell)?;
+
+    Ok(())
+}
+
+/// Push `package` after all of its runtime and build dependencies.
+fn dependency_order<'a>(
+    packages: &'a Packages,
+    package: &'a Package,
+    order: &mut Vec<&'a Package>,
+    exists: &mut HashSet<&'a Package>,
+) {
+    if exists.contains(package) {
+        return;
+    }
+
+    exists.insert(package);
+
+    for (requirement, _build) in crate::tree::edges(package) {
+        let matches = packages.matches(requirement).collect::<Vec<_>>();
+
+        if let [dependency] = matches.as_slice() {
+            dependency_order(packages, *dependency, order, exists);
+        }
+    }
+
+    order.push(package);
+}
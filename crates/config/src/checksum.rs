@@ -0,0 +1,255 @@
+use crate::Result;
+use path::Path;
+use sha2::Digest;
+use std::fmt;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+/// Bytes read at a time while hashing a file.
+const CHUNK: usize = 64 * 1024;
+
+/// A digest a download has to match.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Checksum {
+    Sha256(String),
+    Blake3(String),
+}
+
+impl Checksum {
+    pub fn algorithm(&self) -> &'static str {
+        match self {
+            Checksum::Sha256(_) => "sha256",
+            Checksum::Blake3(_) => "blake3",
+        }
+    }
+
+    /// Expected digest, in hex.
+    pub fn digest(&self) -> &str {
+        match self {
+            Checksum::Sha256(digest) | Checksum::Blake3(digest) => digest.as_str(),
+        }
+    }
+
+    pub(crate) fn hasher(&self) -> Hasher {
+        match self {
+            Checksum::Sha256(_) => Hasher::Sha256(sha2::Sha256::new()),
+            Checksum::Blake3(_) => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+}
+
+impl fmt::Display for Checksum {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{}:{}", self.algorithm(), self.digest()))
+    }
+}
+
+/// Computes the digest of a [`Checksum`] incrementally.
+pub(crate) enum Hasher {
+    Sha256(sha2::Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(bytes),
+            Hasher::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+        }
+    }
+
+    /// The digest, in hex.
+    pub(crate) fn finalize(self) -> String {
+        match self {
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Compare what `hashers` computed against `checksums`, naming `what` on a mismatch.
+pub(crate) fn compare(checksums: &[Checksum], hashers: Vec<Hasher>, what: &str) -> Result<()> {
+    for (checksum, hasher) in checksums.iter().zip(hashers) {
+        let actual = hasher.finalize();
+
+        if !actual.eq_ignore_ascii_case(checksum.digest()) {
+            return Err(format!(
+                "{} checksum mismatch for {}, expected {}, got {}:{}",
+                checksum.algorithm(),
+                what,
+                checksum,
+                checksum.algorithm(),
+                actual
+            )
+            .into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Check the file at `path` against every one of `checksums`.
+pub async fn verify_file(path: impl AsRef<Path>, checksums: &[Checksum]) -> Result<()> {
+    if checksums.is_empty() {
+        return Ok(());
+    }
+
+    let path = path.as_ref();
+    let mut hashers = checksums.iter().map(Checksum::hasher).collect::<Vec<_>>();
+
+    hash_file(path, &mut hashers).await?;
+
+    compare(checksums, hashers, &path.to_string())
+}
+
+/// SHA-256 digest of the file at `path`, in hex.
+pub async fn sha256_file(path: impl AsRef<Path>) -> Result<String> {
+    let mut hashers = [Hasher::Sha256(sha2::Sha256::new())];
+
+    hash_file(path.as_ref(), &mut hashers).await?;
+
+    let [hasher] = hashers;
+
+    Ok(hasher.finalize())
+}
+
+/// Feed the file at `path` through every one of `hashers`, without reading it into memory.
+async fn hash_file(path: &Path, hashers: &mut [Hasher]) -> Result<()> {
+    let mut file = File::open(path)
+        .await
+        .map_err(|error| format!("unable to read {}: {}", path, error))?;
+    let mut buffer = vec![0; CHUNK];
+
+    loop {
+        let read = file.read(&mut buffer).await?;
+
+        if read == 0 {
+            break;
+        }
+
+        for hasher in hashers.iter_mut() {
+            hasher.update(&buffer[..read]);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sha256_file, verify_file, Checksum, CHUNK};
+    use path::{Path, PathBuf};
+
+    const ABC_SHA256: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const ABC_BLAKE3: &str = "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85";
+    const EMPTY_BLAKE3: &str = "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262";
+
+    /// SHA-256 of a million `a`s.
+    const MILLION_SHA256: &str = "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0";
+
+    async fn file(name: &str, contents: &[u8]) -> PathBuf {
+        let path = Path::new(std::env::temp_dir().to_str().unwrap()).join(format!(
+            "mix-checksum-{}-{}",
+            name,
+            std::process::id()
+        ));
+
+        tokio::fs::write(&path, contents).await.unwrap();
+
+        path
+    }
+
+    fn digest(checksum: Checksum, bytes: &[u8]) -> String {
+        let mut hasher = checksum.hasher();
+
+        hasher.update(bytes);
+        hasher.finalize()
+    }
+
+    #[test]
+    fn known_answers() {
+        assert_eq!(digest(Checksum::Sha256(String::new()), b"abc"), ABC_SHA256);
+        assert_eq!(digest(Checksum::Blake3(String::new()), b"abc"), ABC_BLAKE3);
+        assert_eq!(digest(Checksum::Blake3(String::new()), b""), EMPTY_BLAKE3);
+    }
+
+    #[tokio::test]
+    async fn streams_files_larger_than_a_chunk() {
+        let contents = vec![b'a'; 1_000_000];
+
+        assert!(contents.len() > CHUNK);
+
+        let path = file("million", &contents).await;
+        let blake3 = blake3::hash(&contents).to_hex().to_string();
+
+        assert_eq!(sha256_file(&path).await.unwrap(), MILLION_SHA256);
+        assert!(verify_file(
+            &path,
+            &[
+                Checksum::Sha256(MILLION_SHA256.to_string()),
+                Checksum::Blake3(blake3),
+            ]
+        )
+        .await
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn digests_ignore_case() {
+        let path = file("case", b"abc").await;
+
+        assert!(
+            verify_file(&path, &[Checksum::Sha256(ABC_SHA256.to_uppercase())])
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn mismatch_is_rejected() {
+        let path = file("mismatch", b"abd").await;
+
+        assert!(
+            verify_file(&path, &[Checksum::Sha256(ABC_SHA256.to_string())])
+                .await
+                .is_err()
+        );
+
+        // Every digest has to match, not just one of them.
+        let path = file("partial", b"abc").await;
+        let checksums = [
+            Checksum::Sha256(ABC_SHA256.to_string()),
+            Checksum::Blake3(EMPTY_BLAKE3.to_string()),
+        ];
+
+        assert!(verify_file(&path, &checksums).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn cached_download_is_checked() {
+        let prefix = Path::new(std::env::temp_dir().to_str().unwrap())
+            .join(format!("mix-checksum-prefix-{}", std::process::id()));
+
+        prefix.create_dir_all_async().await.unwrap();
+
+        let config = crate::Config::new(&prefix).await.unwrap();
+        let path = file("cached", b"abd").await;
+        let checksums = [Checksum::Sha256(ABC_SHA256.to_string())];
+
+        // The file exists, so nothing is downloaded, yet it still has to match.
+        assert!(config
+            .download_file_checked(&path, "https://example.com/abc.tar.gz", &checksums)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_an_error() {
+        let path = Path::new(std::env::temp_dir().to_str().unwrap())
+            .join(format!("mix-checksum-missing-{}", std::process::id()));
+
+        assert!(sha256_file(&path).await.is_err());
+    }
+}
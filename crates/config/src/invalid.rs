@@ -0,0 +1,183 @@
+use mix_shell::{Diagnostic, Label};
+use std::fmt;
+use std::ops::Range;
+
+/// A YAML document which doesn't parse, and where.
+#[derive(Debug)]
+pub struct Invalid {
+    /// Path of the offending field, such as `depend[2]` or `build.system`, empty when the
+    /// document isn't valid YAML.
+    pub field: String,
+    pub message: String,
+    /// Byte range of the offending text.
+    pub span: Range<usize>,
+    /// One-based.
+    pub line: usize,
+    /// One-based, in characters.
+    pub column: usize,
+    /// The document, to render the span against.
+    pub text: String,
+}
+
+impl Invalid {
+    pub fn new(text: &str, error: serde_path_to_error::Error<serde_yaml::Error>) -> Self {
+        let field = match error.path().to_string() {
+            path if path == "." => String::new(),
+            path => path,
+        };
+
+        let error = error.into_inner();
+        let message = error.to_string();
+
+        // serde_yaml appends the location, which is reported separately.
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _location)) => message.to_string(),
+            None => message,
+        };
+
+        // serde_yaml counts characters, not bytes.
+        let start = match error.location() {
+            Some(location) => text
+                .char_indices()
+                .nth(location.index())
+                .map_or(text.len(), |(offset, _char)| offset),
+            None => field_offset(text, &field).unwrap_or(0),
+        };
+
+        let rest = &text[start..];
+        let end = start + rest.find('\n').unwrap_or(rest.len());
+        let end = start + text[start..end].trim_end().len();
+        let before = &text[..start];
+        let line_start = before.rfind('\n').map_or(0, |index| index + 1);
+        let line = before.matches('\n').count() + 1;
+        let column = before[line_start..].chars().count() + 1;
+
+        Self {
+            field,
+            message,
+            span: start..end,
+            line,
+            column,
+            text: text.to_string(),
+        }
+    }
+
+    /// A diagnostic pointing at the offending text, `what` names the document.
+    pub fn diagnostic(&self, what: &str) -> Diagnostic<()> {
+        let label = if self.field.is_empty() {
+            self.message.clone()
+        } else {
+            format!("invalid `{}`: {}", self.field, self.message)
+        };
+
+        Diagnostic::error()
+            .with_message(format!("failed to parse {}", what))
+            .with_labels(vec![
+                Label::primary((), self.span.clone()).with_message(label)
+            ])
+    }
+}
+
+/// Offset of the top level key `field` starts within, for errors serde_yaml doesn't locate.
+fn field_offset(text: &str, field: &str) -> Option<usize> {
+    let key = field.split(|c| c == '.' || c == '[').next()?;
+
+    if key.is_empty() {
+        return None;
+    }
+
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        if line.starts_with(key) && line[key.len()..].trim_start().starts_with(':') {
+            return Some(offset);
+        }
+
+        offset += line.len();
+    }
+
+    None
+}
+
+impl fmt::Display for Invalid {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{}:{}: ", self.line, self.column))?;
+
+        if !self.field.is_empty() {
+            fmt.write_fmt(format_args!("{}: ", self.field))?;
+        }
+
+        fmt.write_str(&self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{field_offset, Invalid};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    #[allow(dead_code)]
+    struct Document {
+        #[serde(default)]
+        name: String,
+        #[serde(default)]
+        count: u32,
+        #[serde(default)]
+        pair: (String, u32),
+    }
+
+    fn invalid(text: &str) -> Invalid {
+        let deserializer = serde_yaml::Deserializer::from_str(text);
+        let error = serde_path_to_error::deserialize::<_, Document>(deserializer).unwrap_err();
+
+        Invalid::new(text, error)
+    }
+
+    #[test]
+    fn span_after_multibyte_lines() {
+        let invalid = invalid("name: héllö\ncount: many\n");
+
+        assert_eq!(invalid.field, "count");
+        assert_eq!(&invalid.text[invalid.span.clone()], "many");
+        assert_eq!((invalid.line, invalid.column), (2, 8));
+    }
+
+    #[test]
+    fn column_counts_characters() {
+        let invalid = invalid("pair: [é, x]\n");
+
+        assert!(invalid.text[invalid.span.clone()].starts_with('x'));
+        assert_eq!((invalid.line, invalid.column), (1, 11));
+    }
+
+    #[test]
+    fn span_of_first_line() {
+        let invalid = invalid("count: -1\n");
+
+        assert_eq!(&invalid.text[invalid.span.clone()], "-1");
+        assert_eq!((invalid.line, invalid.column), (1, 8));
+    }
+
+    #[test]
+    fn field_offset_of_nested_field() {
+        let text = "name: x\nbuild:\n  system: y\n";
+
+        assert_eq!(field_offset(text, "build.system"), Some(8));
+        assert_eq!(field_offset(text, "name"), Some(0));
+    }
+
+    #[test]
+    fn field_offset_of_indexed_field() {
+        let text = "build-depend: [a]\ndepend: [a, b, c]\n";
+
+        assert_eq!(field_offset(text, "depend[2]"), Some(18));
+    }
+
+    #[test]
+    fn field_offset_without_key() {
+        assert_eq!(field_offset("name: x\n", ""), None);
+        assert_eq!(field_offset("name: x\n", "count"), None);
+        assert_eq!(field_offset("names: x\n", "name"), None);
+    }
+}
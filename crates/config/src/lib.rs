@@ -1,8 +1,9 @@
 #![feature(format_args_nl)]
 
+use crate::checksum::{Checksum, Hasher};
 use crate::settings::Settings;
 use futures_util::stream::StreamExt;
-use mix_id::RepositoryId;
+use mix_id::{PackageId, RepositoryId};
 use mix_shell::{write, writeln, AsyncWrite, Shell};
 use path::{Path, PathBuf};
 use std::collections::BTreeMap;
@@ -17,6 +18,8 @@ use url::Url;
 pub type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
+pub mod checksum;
+pub mod invalid;
 pub mod settings;
 
 #[derive(Debug)]
@@ -33,12 +36,21 @@ struct ConfigRef {
     /// repos prefix
     repos_prefix: PathBuf,
 
+    /// settings file
+    settings_path: PathBuf,
+
     /// input/output handler
     shell: Shell,
 
     /// repositories to sync
     repositories: BTreeMap<RepositoryId, Url>,
 
+    /// repositories in order of preference
+    priority: Vec<RepositoryId>,
+
+    /// packages held to a version requirement
+    holds: BTreeMap<String, mix_version::Requirement>,
+
     /// http client
     http: reqwest::Client,
 }
@@ -59,8 +71,21 @@ impl Config {
             settings_path.write_async("").await?;
         }
 
-        let settings_string = settings_path.read_to_string_async().await?;
-        let settings = Settings::parse(settings_string.as_str())?;
+        let settings = match Settings::load(&settings_path).await {
+            Ok(settings) => settings,
+            Err(settings::Error::Invalid(invalid)) => {
+                shell
+                    .emit(
+                        settings_path.as_str(),
+                        &invalid.text,
+                        &invalid.diagnostic("settings"),
+                    )
+                    .await?;
+
+                return Err(format!("unable to load {}", settings_path).into());
+            }
+            Err(error) => return Err(format!("{}: {}", settings_path, error).into()),
+        };
 
         let http = reqwest::Client::builder()
             .user_agent(concat!(
@@ -75,8 +100,11 @@ impl Config {
             build_prefix,
             cache_prefix,
             repos_prefix,
+            settings_path,
             shell,
             repositories: settings.repositories,
+            priority: settings.priority,
+            holds: settings.holds,
             http,
         })))
     }
@@ -101,6 +129,11 @@ impl Config {
         self.0.repos_prefix.as_path()
     }
 
+    /// settings file
+    pub fn settings_path(&self) -> &Path {
+        self.0.settings_path.as_path()
+    }
+
     /// input/output handler
     pub fn shell(&self) -> &Shell {
         &self.0.shell
@@ -110,13 +143,68 @@ impl Config {
         &self.0.repositories
     }
 
+    /// Priority of a repository, lower is preferred.
+    ///
+    /// Repositories missing from `priority` in `settings.yml` share the lowest priority.
+    pub fn priority(&self, repository_id: &RepositoryId) -> usize {
+        self.0
+            .priority
+            .iter()
+            .position(|id| id == repository_id)
+            .unwrap_or(usize::MAX)
+    }
+
+    /// Version requirement a package is held to in `settings.yml`, if any.
+    pub fn hold(
+        &self,
+        repository_id: &RepositoryId,
+        package_id: &PackageId,
+    ) -> Option<&mix_version::Requirement> {
+        self.0
+            .holds
+            .get(&format!("{}/{}", repository_id, package_id))
+    }
+
+    /// Download `url` to `path`, unless `path` already exists.
     pub async fn download_file(&self, path: impl AsRef<Path>, url: impl AsRef<str>) -> Result<()> {
+        self.download_file_checked(path, url, &[]).await
+    }
+
+    /// Download `url` to `path`, unless `path` already exists, checking it against `checksums`
+    /// either way.
+    pub async fn download_file_checked(
+        &self,
+        path: impl AsRef<Path>,
+        url: impl AsRef<str>,
+        checksums: &[Checksum],
+    ) -> Result<()> {
         let path = path.as_ref();
 
         if path.exists() {
-            return Ok(());
+            return checksum::verify_file(path, checksums).await;
         }
 
+        self.fetch_file_checked(path, url, checksums).await
+    }
+
+    /// Download `url` to `path`, replacing `path` once the download has completed.
+    pub async fn fetch_file(&self, path: impl AsRef<Path>, url: impl AsRef<str>) -> Result<()> {
+        self.fetch_file_checked(path, url, &[]).await
+    }
+
+    /// Download `url` to `path`, replacing `path` once the download has completed and matches
+    /// every one of `checksums`.
+    pub async fn fetch_file_checked(
+        &self,
+        path: impl AsRef<Path>,
+        url: impl AsRef<str>,
+        checksums: &[Checksum],
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let mut hashers = checksums
+            .iter()
+            .map(Checksum::hasher)
+            .collect::<Vec<Hasher>>();
         let mut partial = path.to_path_buf();
         let file_name = path.file_name().unwrap_or_else(|| Path::new("<unknown>"));
         let url = url.as_ref();
@@ -135,7 +223,7 @@ impl Config {
         let mut interval = time::interval(Duration::from_millis(50));
         interval.tick().await;
         let mut destination = File::create(&partial).await?;
-        let response = self.0.http.get(url).send().await?;
+        let response = self.0.http.get(url).send().await?.error_for_status()?;
         let mut stream = response.bytes_stream();
 
         loop {
@@ -150,6 +238,10 @@ impl Config {
 
                     downloaded += bytes.len();
                     destination.write_all(bytes).await?;
+
+                    for hasher in hashers.iter_mut() {
+                        hasher.update(bytes);
+                    }
                 } else {
                     break;
                 }
@@ -157,6 +249,16 @@ impl Config {
         }
 
         destination.flush().await?;
+
+        if let Err(error) = checksum::compare(checksums, hashers, url) {
+            writeln!(self.shell())?;
+            self.shell().flush().await?;
+
+            let _ = tokio::fs::remove_file(&partial).await;
+
+            return Err(error);
+        }
+
         partial.rename_async(path).await?;
 
         writeln!(
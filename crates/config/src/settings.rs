@@ -1,34 +1,58 @@
+use crate::invalid::Invalid;
 use mix_id::RepositoryId;
+use path::Path;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::str::FromStr;
 use std::{error, fmt, io};
 use url::Url;
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct Settings {
     #[serde(default, rename = "repos")]
     pub repositories: BTreeMap<RepositoryId, Url>,
+
+    /// Repositories in order of preference, for requirements without a repository.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub priority: Vec<RepositoryId>,
+
+    /// Version requirements packages are held to, keyed by `repository/package`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub holds: BTreeMap<String, mix_version::Requirement>,
+
+    /// Keys mix doesn't know about, kept so they survive a save.
+    #[serde(flatten)]
+    pub other: BTreeMap<String, serde_yaml::Value>,
 }
 
 impl Settings {
+    /// Parse settings, locating the offending field on failure.
     pub fn parse(text: &str) -> Result<Self, Error> {
-        let this: Self = match serde_yaml::from_str(text) {
-            Ok(this) => this,
-            Err(error) => {
-                let debug = format!("{:?}", error);
-
-                if debug.contains("EndOfStream") {
-                    Self {
-                        repositories: BTreeMap::new(),
-                    }
-                } else {
-                    return Err(error.into());
-                }
-            }
-        };
-
-        Ok(this)
+        // serde_yaml refuses an empty document, which is what a fresh settings.yml is.
+        if text.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        let deserializer = serde_yaml::Deserializer::from_str(text);
+
+        serde_path_to_error::deserialize(deserializer)
+            .map_err(|error| Error::Invalid(Invalid::new(text, error)))
+    }
+
+    /// Read and parse the settings at `path`.
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let text = path.as_ref().read_to_string_async().await?;
+
+        Self::parse(text.as_str())
+    }
+
+    /// Serialize and write these settings to `path`.
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let text = serde_yaml::to_string(self)?;
+
+        path.as_ref().write_async(text.as_str()).await?;
+
+        Ok(())
     }
 }
 
@@ -43,6 +67,7 @@ impl FromStr for Settings {
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
+    Invalid(Invalid),
     Serde(serde_yaml::Error),
 }
 
@@ -52,7 +77,6 @@ impl From<io::Error> for Error {
     }
 }
 
-// TODO: Reconstruct error from display of serde_yaml::Error.
 impl From<serde_yaml::Error> for Error {
     fn from(error: serde_yaml::Error) -> Self {
         Error::Serde(error)
@@ -65,6 +89,7 @@ impl fmt::Display for Error {
 
         match &self {
             Io(error) => fmt.write_fmt(format_args!("{}", error))?,
+            Invalid(error) => fmt.write_fmt(format_args!("{}", error))?,
             Serde(error) => fmt.write_fmt(format_args!("{}", error))?,
         }
 
@@ -0,0 +1,310 @@
+#![feature(format_args_nl)]
+
+use futures_util::stream::TryStreamExt;
+use mix_id::{PackageId, RepositoryId};
+use mix_manifest::Manifest;
+use mix_packages::{read_dirs, report, Packages};
+use mix_shell::{header, AsyncWrite};
+use mix_triple::Triple;
+use mix_version::Version;
+use path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+const PARTIAL: &str = ".partial";
+
+/// Virtual packages `mix_build` needs, and the install under `core` it falls back to.
+const TOOLCHAIN: [(&str, &str, &str); 2] = [("libc", "glibc", "2.34.0"), ("cc", "gcc", "11.2.0")];
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub target: Triple,
+    pub fix: bool,
+}
+
+#[derive(Debug)]
+enum Problem {
+    /// Installed, but without a manifest in any repository.
+    Orphaned(PathBuf),
+
+    /// A download that never completed.
+    Partial(PathBuf),
+
+    /// A build directory for a version that isn't installed.
+    StaleBuild(PathBuf),
+
+    /// A manifest that fails to parse, so its package is ignored.
+    BrokenManifest(PathBuf, Error),
+
+    /// A patch a manifest lists, missing from the package's `patches/` directory.
+    MissingPatch(PathBuf),
+
+    /// A toolchain install the build depends on.
+    MissingToolchain(PathBuf),
+}
+
+impl Problem {
+    /// Whether this problem can be fixed by deleting the path.
+    fn is_fixable(&self) -> bool {
+        matches!(self, Problem::Partial(_) | Problem::StaleBuild(_))
+    }
+}
+
+/// Check the prefix for problems, and optionally clean up the safe cases.
+pub async fn doctor(
+    config: mix_config::Config,
+    doctor_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let packages = packages.for_target(doctor_config.target);
+    let mut problems = Vec::new();
+
+    check_orphans(&packages, &mut problems).await;
+    check_manifests(&config, &mut problems).await?;
+    check_partials(config.cache_prefix(), &mut problems).await?;
+    check_builds(&config, &packages, &mut problems).await?;
+    check_patches(&packages, &mut problems).await;
+    check_toolchain(&doctor_config, &packages, &mut problems).await;
+
+    let shell = config.shell();
+
+    for problem in problems.iter() {
+        match problem {
+            Problem::Orphaned(path) => header!(
+                shell,
+                "{} {} has no manifest",
+                shell.theme().warning_paint("orphaned"),
+                path
+            )?,
+            Problem::Partial(path) => header!(
+                shell,
+                "{} {}",
+                shell.theme().warning_paint("partial download"),
+                path
+            )?,
+            Problem::StaleBuild(path) => header!(
+                shell,
+                "{} {} has no install",
+                shell.theme().warning_paint("stale build"),
+                path
+            )?,
+            Problem::BrokenManifest(path, error) => report(&config, path, error).await?,
+            Problem::MissingPatch(path) => header!(
+                shell,
+                "{} {}",
+                shell.theme().error_paint("missing patch"),
+                path
+            )?,
+            Problem::MissingToolchain(path) => header!(
+                shell,
+                "{} {}",
+                shell.theme().error_paint("missing toolchain"),
+                path
+            )?,
+        }
+    }
+
+    if problems.is_empty() {
+        header!(shell, "{}", "no problems found")?;
+
+        return Ok(());
+    }
+
+    if !doctor_config.fix {
+        header!(shell, "{} problem(s) found", problems.len())?;
+
+        return Ok(());
+    }
+
+    for problem in problems.iter().filter(|problem| problem.is_fixable()) {
+        match problem {
+            Problem::Partial(path) => tokio::fs::remove_file(path).await?,
+            Problem::StaleBuild(path) => tokio::fs::remove_dir_all(path).await?,
+            _ => continue,
+        }
+
+        header!(
+            shell,
+            "{} {}",
+            shell.theme().command_paint("removed"),
+            path_of(problem)
+        )?;
+    }
+
+    Ok(())
+}
+
+fn path_of(problem: &Problem) -> &Path {
+    match problem {
+        Problem::Orphaned(path)
+        | Problem::Partial(path)
+        | Problem::StaleBuild(path)
+        | Problem::BrokenManifest(path, _)
+        | Problem::MissingPatch(path)
+        | Problem::MissingToolchain(path) => path.as_path(),
+    }
+}
+
+/// Installed packages whose manifest is gone, see `PackageRef::new_orphaned`.
+async fn check_orphans(packages: &Packages, problems: &mut Vec<Problem>) {
+    for package in packages.installed().iter() {
+        if package.manifest_path().exists_async().await {
+            continue;
+        }
+
+        for path in package.versions().paths() {
+            problems.push(Problem::Orphaned(path.to_path_buf()));
+        }
+    }
+}
+
+/// Manifests which `Packages::from_config` skips.
+async fn check_manifests(config: &mix_config::Config, problems: &mut Vec<Problem>) -> Result<()> {
+    let mut repository_dirs = read_dirs(config.repos_prefix()).await?;
+
+    while let Some(repository_dir) = repository_dirs.try_next().await? {
+        let mut package_dirs = read_dirs(repository_dir.as_path()).await?;
+
+        while let Some(package_dir) = package_dirs.try_next().await? {
+            let manifest_path = package_dir.join("manifest.yml");
+
+            if !manifest_path.exists_async().await {
+                continue;
+            }
+
+            let manifest = manifest_path.read_to_string_async().await?;
+
+            if let Err(error) = Manifest::from_str(manifest.as_str()) {
+                problems.push(Problem::BrokenManifest(manifest_path, error.into()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Leftover `.partial` files anywhere in the cache.
+async fn check_partials(cache_prefix: &Path, problems: &mut Vec<Problem>) -> Result<()> {
+    let mut dirs = vec![cache_prefix.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        if !dir.exists_async().await {
+            continue;
+        }
+
+        let mut entries = dir.read_dir_async().await?;
+
+        while let Some(entry) = entries.try_next().await? {
+            let path = entry.path();
+
+            if entry.is_dir_async().await {
+                dirs.push(path);
+            } else if path.to_string().ends_with(PARTIAL) {
+                problems.push(Problem::Partial(path));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Build directories (`build/<repository>/<package>/<version>`) without a matching install.
+async fn check_builds(
+    config: &mix_config::Config,
+    packages: &Packages,
+    problems: &mut Vec<Problem>,
+) -> Result<()> {
+    if !config.build_prefix().exists_async().await {
+        return Ok(());
+    }
+
+    let mut repository_dirs = read_dirs(config.build_prefix()).await?;
+
+    while let Some(repository_dir) = repository_dirs.try_next().await? {
+        let repository_id = match repository_dir
+            .file_name()
+            .and_then(|id| id.to_str())
+            .and_then(|id| RepositoryId::try_from(id).ok())
+        {
+            Some(id) => id,
+            None => continue,
+        };
+
+        let mut package_dirs = read_dirs(repository_dir.as_path()).await?;
+
+        while let Some(package_dir) = package_dirs.try_next().await? {
+            let package_id = match package_dir
+                .file_name()
+                .and_then(|id| id.to_str())
+                .and_then(|id| PackageId::try_from(id).ok())
+            {
+                Some(id) => id,
+                None => continue,
+            };
+
+            let package = packages.get(&repository_id, &package_id);
+            let mut version_dirs = read_dirs(package_dir.as_path()).await?;
+
+            while let Some(version_dir) = version_dirs.try_next().await? {
+                let version = match version_dir
+                    .file_name()
+                    .and_then(|version| version.to_str())
+                    .and_then(|version| Version::parse(version).ok())
+                {
+                    Some(version) => version,
+                    None => continue,
+                };
+
+                if !package.map_or(false, |package| package.versions().contains(&version)) {
+                    problems.push(Problem::StaleBuild(version_dir));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Patches manifests list, which would only fail once their package is built.
+async fn check_patches(packages: &Packages, problems: &mut Vec<Problem>) {
+    for package in packages.iter() {
+        for patch in package.patches().into_iter().flatten() {
+            let path = package.patches_dir().join(&patch.file);
+
+            if !path.exists_async().await {
+                problems.push(Problem::MissingPatch(path));
+            }
+        }
+    }
+}
+
+/// Toolchain installs `mix_build` needs for the target, either from a provider or the fallback
+/// install.
+async fn check_toolchain(doctor_config: &Config, packages: &Packages, problems: &mut Vec<Problem>) {
+    for (provides, package_id, version) in TOOLCHAIN {
+        let provided = PackageId::try_from(provides).map_or(false, |provides| {
+            packages
+                .providers_iter(&provides)
+                .any(|package| package.installed())
+        });
+
+        if provided {
+            continue;
+        }
+
+        let path = doctor_config
+            .prefix
+            .join(doctor_config.target.as_str())
+            .join(RepositoryId::CORE.as_str())
+            .join(package_id)
+            .join(version);
+
+        if !path.is_dir_async().await {
+            problems.push(Problem::MissingToolchain(path));
+        }
+    }
+}
@@ -0,0 +1,284 @@
+#![feature(format_args_nl)]
+
+use mix_atom::Requirement;
+use mix_packages::{Package, Packages};
+use mix_shell::{header, writeln, AsyncWrite};
+use mix_triple::Triple;
+use path::PathBuf;
+use std::collections::BTreeSet;
+use std::os::unix::process::CommandExt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::{env, fmt, process};
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+const BASH: &str = "bash";
+const FISH: &str = "fish";
+const ZSH: &str = "zsh";
+
+const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// Environment variables and the install sub-directories they point to.
+const VARIABLES: [(&str, &[&str]); 5] = [
+    ("PATH", &["bin", "sbin"]),
+    ("LD_LIBRARY_PATH", &["lib", "lib64"]),
+    (
+        "PKG_CONFIG_PATH",
+        &["lib/pkgconfig", "lib64/pkgconfig", "share/pkgconfig"],
+    ),
+    ("CPATH", &["include"]),
+    ("MANPATH", &["share/man"]),
+];
+
+/// A shell to print `export` lines for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+    Bash,
+    Fish,
+    Zsh,
+}
+
+impl Kind {
+    /// returns this shell as a string
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Bash => BASH,
+            Kind::Fish => FISH,
+            Kind::Zsh => ZSH,
+        }
+    }
+
+    /// Format a single variable assignment for this shell.
+    pub fn export(&self, key: &str, value: &str) -> String {
+        let value = value.replace('\'', r#"'\''"#);
+
+        match self {
+            Kind::Bash | Kind::Zsh => format!("export {}='{}'", key, value),
+            Kind::Fish => format!("set -gx {} '{}'", key, value),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.write_str(self.as_str())
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum KindParseError {
+    InvalidShell(Box<str>),
+}
+
+impl fmt::Display for KindParseError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KindParseError::InvalidShell(shell) => {
+                write!(fmt, "unsupported shell `{}`", shell.trim())
+            }
+        }
+    }
+}
+
+impl std::error::Error for KindParseError {}
+
+impl FromStr for Kind {
+    type Err = KindParseError;
+
+    fn from_str(kind: &str) -> Result<Self, Self::Err> {
+        let kind = match kind {
+            BASH => Kind::Bash,
+            FISH => Kind::Fish,
+            ZSH => Kind::Zsh,
+            _ => return Err(KindParseError::InvalidShell(kind.into())),
+        };
+
+        Ok(kind)
+    }
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub target: Triple,
+    pub requirements: Vec<Requirement>,
+    pub print: Option<Kind>,
+}
+
+/// Spawn `$SHELL`, or print `export` lines, with an environment for the given packages.
+pub async fn env(
+    config: mix_config::Config,
+    env_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let packages = packages.for_target(env_config.target);
+    let mut installs = Vec::new();
+    let mut missing = Vec::new();
+    let mut exists = BTreeSet::new();
+
+    for requirement in env_config.requirements.iter() {
+        let matches = packages
+            .matches(requirement)
+            .filter(|package| package.installed())
+            .collect::<Vec<_>>();
+
+        let package = match matches.as_slice() {
+            [package] => *package,
+            [] => return Err(format!("{} is not installed", requirement).into()),
+            _ => return Err(format!("{} is ambiguous", requirement).into()),
+        };
+
+        resolve(
+            &env_config,
+            &packages,
+            package,
+            requirement,
+            &mut installs,
+            &mut missing,
+            &mut exists,
+        );
+    }
+
+    for reason in missing {
+        // Printed environments are meant to be `eval`ed, keep warnings as comments.
+        if env_config.print.is_some() {
+            writeln!(config.shell(), "# {}", reason)?;
+        } else {
+            header!(
+                config.shell(),
+                "{} {}",
+                config.shell().theme().warning_paint("warning"),
+                reason
+            )?;
+        }
+    }
+
+    let variables = variables(&installs).await;
+
+    match env_config.print {
+        Some(kind) => {
+            for (key, value) in variables.iter() {
+                writeln!(config.shell(), "{}", kind.export(key, value))?;
+            }
+
+            config.shell().flush().await?;
+
+            Ok(())
+        }
+        None => {
+            let shell = env::var_os("SHELL").unwrap_or_else(|| DEFAULT_SHELL.into());
+
+            config.shell().flush().await?;
+
+            // Only returns if the shell failed to execute.
+            let error = process::Command::new(shell).envs(variables).exec();
+
+            Err(error.into())
+        }
+    }
+}
+
+/// Collect install directories for `package` and its installed transitive dependencies.
+///
+/// Requirements nothing installed satisfies are left out, with the reason added to `missing`.
+fn resolve<'a>(
+    env_config: &Config,
+    packages: &'a Packages,
+    package: &'a Package,
+    requirement: &'a Requirement,
+    installs: &mut Vec<PathBuf>,
+    missing: &mut Vec<String>,
+    exists: &mut BTreeSet<&'a Package>,
+) {
+    if exists.contains(package) {
+        return;
+    }
+
+    exists.insert(package);
+
+    let version = match package.versions().matches(&requirement.requirement).last() {
+        Some(version) => version,
+        None => {
+            let installed = package
+                .versions()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            missing.push(format!(
+                "{} is not installed, {}/{} has {}",
+                requirement,
+                package.repository_id(),
+                package.package_id(),
+                installed
+            ));
+
+            return;
+        }
+    };
+
+    installs.push(
+        env_config
+            .prefix
+            .join(env_config.target.as_str())
+            .join(package.repository_id().as_str())
+            .join(package.package_id().as_str())
+            .join(version.to_string()),
+    );
+
+    for requirement in package.dependencies() {
+        let dependency = packages
+            .matches(requirement)
+            .find(|dependency| dependency.installed());
+
+        match dependency {
+            Some(dependency) => resolve(
+                env_config,
+                packages,
+                dependency,
+                requirement,
+                installs,
+                missing,
+                exists,
+            ),
+            None => missing.push(format!("{} is not installed", requirement)),
+        }
+    }
+}
+
+/// Build search path variables from existing directories, prepended to the current environment.
+async fn variables(installs: &[PathBuf]) -> Vec<(&'static str, String)> {
+    let mut variables = Vec::new();
+
+    for (key, subdirs) in VARIABLES {
+        let mut paths = Vec::new();
+
+        for install in installs {
+            for subdir in subdirs {
+                let dir = install.join(subdir);
+
+                if dir.is_dir_async().await {
+                    paths.push(dir.to_string());
+                }
+            }
+        }
+
+        if paths.is_empty() {
+            continue;
+        }
+
+        match env::var(key) {
+            Ok(value) if !value.is_empty() => paths.push(value),
+            // An empty entry keeps `man`'s default search path.
+            _ if key == "MANPATH" => paths.push(String::new()),
+            _ => {}
+        }
+
+        variables.push((key, paths.join(":")));
+    }
+
+    variables
+}
@@ -0,0 +1,125 @@
+#![feature(format_args_nl)]
+
+use mix_atom::Requirement;
+use mix_config::settings::Settings;
+use mix_packages::{Package, Packages};
+use mix_shell::{header, AsyncWrite};
+use path::PathBuf;
+use std::sync::Arc;
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub requirements: Vec<Requirement>,
+}
+
+/// Hold packages to a version requirement in `settings.yml`.
+///
+/// A requirement without a version holds the package at its newest installed version.
+pub async fn hold(
+    config: mix_config::Config,
+    hold_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let shell = config.shell();
+    let mut settings = Settings::load(config.settings_path()).await?;
+
+    for requirement in hold_config.requirements.iter() {
+        let package = resolve(&packages, requirement)?;
+        let hold = if requirement.requirement.is_star() {
+            let installed = package.versions().latest().ok_or_else(|| {
+                format!(
+                    "{}/{} is not installed, specify a version to hold it at",
+                    package.repository_id(),
+                    package.package_id()
+                )
+            })?;
+
+            mix_version::Requirement::parse(&format!("={}", installed))?
+        } else {
+            requirement.requirement.clone()
+        };
+
+        let name = format!("{}/{}", package.repository_id(), package.package_id());
+
+        header!(
+            shell,
+            "{} {}:{}",
+            shell.theme().command_paint("held"),
+            shell.theme().arguments_paint(&name),
+            hold
+        )?;
+
+        settings.holds.insert(name, hold);
+    }
+
+    settings.save(config.settings_path()).await?;
+
+    Ok(())
+}
+
+/// Remove the holds on packages from `settings.yml`.
+pub async fn unhold(
+    config: mix_config::Config,
+    hold_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let shell = config.shell();
+    let mut settings = Settings::load(config.settings_path()).await?;
+
+    for requirement in hold_config.requirements.iter() {
+        // Allow removing holds on packages which are gone from their repository.
+        let name = match &requirement.repository_id {
+            Some(repository_id) => format!("{}/{}", repository_id, requirement.package_id),
+            None => {
+                let package = resolve(&packages, requirement)?;
+
+                format!("{}/{}", package.repository_id(), package.package_id())
+            }
+        };
+
+        if settings.holds.remove(&name).is_none() {
+            return Err(format!("{} is not held", name).into());
+        }
+
+        header!(
+            shell,
+            "{} {}",
+            shell.theme().command_paint("unheld"),
+            shell.theme().arguments_paint(&name)
+        )?;
+    }
+
+    settings.save(config.settings_path()).await?;
+
+    Ok(())
+}
+
+/// The one package `requirement` names, ignoring packages which only provide it.
+fn resolve<'a>(packages: &'a Packages, requirement: &Requirement) -> Result<&'a Package> {
+    let matches = packages
+        .matches(requirement)
+        .filter(|package| package.package_id() == &requirement.package_id)
+        .collect::<Vec<_>>();
+
+    match matches.as_slice() {
+        [] => Err(format!("no package matches {}", requirement).into()),
+        [package] => Ok(*package),
+        _ => {
+            let candidates = matches
+                .iter()
+                .map(|package| format!("{}/{}", package.repository_id(), package.package_id()))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            Err(format!(
+                "{} is ambiguous, candidates are: {}",
+                requirement, candidates
+            )
+            .into())
+        }
+    }
+}
@@ -0,0 +1,241 @@
+#![feature(format_args_nl)]
+
+use mix_atom::Requirement;
+use mix_packages::Packages;
+use mix_shell::{header, write, writeln, AsyncDisplay, AsyncWrite};
+use mix_triple::Triple;
+use path::PathBuf;
+use std::sync::Arc;
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub target: Triple,
+    pub requirement: Requirement,
+}
+
+/// Show the manifest, metadata, sources, dependencies and install state of matching packages.
+pub async fn info(
+    config: mix_config::Config,
+    info_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let shell = config.shell();
+    let packages = packages.for_target(info_config.target);
+    let matches = packages
+        .matches(&info_config.requirement)
+        .collect::<Vec<_>>();
+
+    if matches.is_empty() {
+        return Err(format!("no package matches {}", &info_config.requirement).into());
+    }
+
+    for package in matches {
+        write!(shell, "{}", shell.theme().header_prefix())?;
+        AsyncDisplay::fmt(package, shell).await?;
+        writeln!(shell)?;
+
+        header!(
+            shell,
+            "  {} {}",
+            shell.theme().command_paint("manifest"),
+            package.manifest_path()
+        )?;
+
+        if let Some(description) = package.description() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("description"),
+                description
+            )?;
+        }
+
+        if let Some(homepage) = package.homepage() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("homepage"),
+                shell.theme().url_paint(homepage)
+            )?;
+        }
+
+        if let Some(license) = package.license() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("license"),
+                shell.theme().arguments_paint(license)
+            )?;
+        }
+
+        for maintainer in package.maintainers() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("maintainer"),
+                maintainer
+            )?;
+        }
+
+        if !package.keywords().is_empty() {
+            let keywords = package
+                .keywords()
+                .iter()
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("keywords"),
+                keywords
+            )?;
+        }
+
+        for source in package.sources().iter() {
+            write!(
+                shell,
+                "{}  {} ",
+                shell.theme().header_prefix(),
+                shell.theme().command_paint("source")
+            )?;
+            AsyncDisplay::fmt(source, shell).await?;
+            writeln!(shell)?;
+        }
+
+        for requirement in package.dependencies() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("depend"),
+                shell.theme().arguments_paint(requirement)
+            )?;
+        }
+
+        for requirement in package.build_dependencies() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("build-depend"),
+                shell.theme().arguments_paint(requirement)
+            )?;
+        }
+
+        for requirement in package.test_dependencies() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("test-depend"),
+                shell.theme().arguments_paint(requirement)
+            )?;
+        }
+
+        for requirement in package.bootstrap() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("bootstrap"),
+                shell.theme().arguments_paint(requirement)
+            )?;
+        }
+
+        for provides in package.provides() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("provides"),
+                shell.theme().arguments_paint(provides)
+            )?;
+        }
+
+        for requirement in package.conflicts() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("conflicts"),
+                shell.theme().arguments_paint(requirement)
+            )?;
+        }
+
+        for requirement in package.replaces() {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("replaces"),
+                shell.theme().arguments_paint(requirement)
+            )?;
+        }
+
+        for (name, option) in package.options() {
+            header!(
+                shell,
+                "  {} {}={}{}",
+                shell.theme().command_paint("option"),
+                shell.theme().arguments_paint(name),
+                &option.default,
+                option
+                    .description
+                    .as_ref()
+                    .map(|description| format!(" {}", description))
+                    .unwrap_or_default()
+            )?;
+        }
+
+        for patch in package.patches().into_iter().flatten() {
+            if patch.version.is_star() {
+                header!(
+                    shell,
+                    "  {} {}",
+                    shell.theme().command_paint("patch"),
+                    &patch.file
+                )?;
+            } else {
+                header!(
+                    shell,
+                    "  {} {} {}",
+                    shell.theme().command_paint("patch"),
+                    &patch.file,
+                    shell.theme().arguments_paint(&patch.version)
+                )?;
+            }
+        }
+
+        if let Some(hold) = config.hold(package.repository_id(), package.package_id()) {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().command_paint("held"),
+                shell.theme().arguments_paint(hold)
+            )?;
+        }
+
+        for (version, path) in package.versions().pairs() {
+            write!(
+                shell,
+                "{}  {} ",
+                shell.theme().header_prefix(),
+                shell.theme().command_paint("installed")
+            )?;
+            AsyncDisplay::fmt(version, shell).await?;
+            writeln!(shell, " {}", path)?;
+        }
+
+        if let Some(latest) = package.upstream_versions(&config).await.latest() {
+            write!(
+                shell,
+                "{}  {} ",
+                shell.theme().header_prefix(),
+                shell.theme().command_paint("latest")
+            )?;
+            AsyncDisplay::fmt(&latest.version, shell).await?;
+            writeln!(shell)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use mix_packages::Packages;
+use mix_shell::{write, writeln, AsyncDisplay, AsyncWrite};
+use mix_triple::Triple;
+use path::PathBuf;
+use std::sync::Arc;
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub target: Triple,
+    pub installed: bool,
+    pub available: bool,
+    pub depend: bool,
+    pub license: bool,
+}
+
+/// List packages, their installed versions, and optionally their licenses and dependencies.
+pub async fn list(
+    config: mix_config::Config,
+    list_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let shell = config.shell();
+    let packages = packages.for_target(list_config.target);
+    let iter = if list_config.installed {
+        packages.installed().iter()
+    } else if list_config.available {
+        packages.not_installed().iter()
+    } else {
+        packages.iter()
+    };
+
+    for package in iter {
+        write!(shell, "{}", shell.theme().header_prefix())?;
+        AsyncDisplay::fmt(package, shell).await?;
+
+        for version in package.versions().iter() {
+            write!(shell, " ")?;
+            AsyncDisplay::fmt(version, shell).await?;
+        }
+
+        if list_config.license {
+            match package.license() {
+                Some(license) => write!(shell, " {}", shell.theme().arguments_paint(license))?,
+                None => write!(shell, " {}", shell.theme().warning_paint("(no license)"))?,
+            }
+        }
+
+        if let Some(hold) = config.hold(package.repository_id(), package.package_id()) {
+            write!(
+                shell,
+                " {}",
+                shell.theme().warning_paint(format!("(held {})", hold))
+            )?;
+        }
+
+        writeln!(shell)?;
+
+        if list_config.depend && !package.dependencies().is_empty() {
+            write!(
+                shell,
+                "{}  {}",
+                shell.theme().header_prefix(),
+                shell.theme().command_paint("depend")
+            )?;
+
+            for requirement in package.dependencies() {
+                write!(shell, " {}", shell.theme().arguments_paint(requirement))?;
+            }
+
+            writeln!(shell)?;
+        }
+    }
+
+    Ok(())
+}
@@ -1,37 +1,330 @@
-use codespan_reporting::diagnostic::{Diagnostic, Label};
-use codespan_reporting::files::SimpleFile;
-use codespan_reporting::term;
-use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
-use codespan_reporting::term::{Chars, Config};
+pub use mix_config::invalid::Invalid;
+
 use mix_atom::Requirement;
+use mix_id::PackageId;
 use mix_source::Source;
-use serde::{Deserialize, Serialize};
-use std::collections::BTreeSet;
+use mix_version::Version;
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
 use std::str::FromStr;
-use std::{error, fmt, io};
+use std::{cmp, error, fmt, io};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Manifest {
+    /// One line summary of what the package is.
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub homepage: Option<String>,
+    /// SPDX license expression, such as `MIT OR Apache-2.0`.
+    #[serde(default)]
+    pub license: Option<String>,
+    /// People looking after the package, as `Name <email>`.
+    #[serde(default)]
+    pub maintainers: Vec<String>,
+    /// Extra words search matches the package by.
+    #[serde(default)]
+    pub keywords: BTreeSet<String>,
+    #[serde(default, rename = "depend")]
+    pub dependencies: BTreeSet<Requirement>,
+    #[serde(default, rename = "build-depend")]
+    pub build_dependencies: BTreeSet<Requirement>,
+    #[serde(default, rename = "test-depend")]
+    pub test_dependencies: BTreeSet<Requirement>,
+    /// Dependencies which must already be installed, they break dependency cycles.
+    #[serde(default)]
+    pub bootstrap: BTreeSet<Requirement>,
+    #[serde(default, rename = "source")]
+    pub sources: BTreeSet<Source>,
+    /// Virtual packages this package provides, such as `cc` or `libc`.
+    #[serde(default)]
+    pub provides: BTreeSet<PackageId>,
+    /// Packages which can't be installed alongside this package.
+    #[serde(default)]
+    pub conflicts: BTreeSet<Requirement>,
+    /// Packages this package supersedes, they're removed once it's installed.
+    #[serde(default)]
+    pub replaces: BTreeSet<Requirement>,
+    /// Blocks keyed by a triple pattern, see `Triple::matches`.
+    #[serde(default, rename = "target")]
+    pub targets: BTreeMap<String, Conditional>,
+    /// How to build this package, detected from the source tree where missing.
+    #[serde(default)]
+    pub build: Build,
+    /// Named build options, set per install with `mix add -D name=value`.
+    #[serde(default)]
+    pub options: BTreeMap<String, BuildOption>,
+    /// Patches from the package's `patches/` directory, in the order they're applied. Every
+    /// patch in the directory is applied, by file name, when missing.
+    #[serde(default)]
+    pub patches: Option<Vec<Patch>>,
+    /// Digests of release tarballs, checked when they're downloaded and before they're used.
+    #[serde(
+        default,
+        rename = "checksum",
+        deserialize_with = "Checksum::deserialize_all"
+    )]
+    pub checksums: Vec<Checksum>,
+}
+
+/// Digests of the tarball of one version, from one source or any of them.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Checksum {
+    pub version: Version,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    /// 64 hex digits, as is `blake3`.
+    #[serde(
+        default,
+        deserialize_with = "Checksum::deserialize_digest",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub sha256: Option<String>,
+    #[serde(
+        default,
+        deserialize_with = "Checksum::deserialize_digest",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub blake3: Option<String>,
+}
+
+impl Checksum {
+    /// Every checksum has to have at least one digest.
+    fn deserialize_all<'de, D>(deserializer: D) -> Result<Vec<Self>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let checksums = Vec::<Self>::deserialize(deserializer)?;
+        let empty = checksums
+            .iter()
+            .find(|checksum| checksum.sha256.is_none() && checksum.blake3.is_none());
+
+        if let Some(checksum) = empty {
+            return Err(D::Error::custom(format!(
+                "checksum of {} has neither sha256 nor blake3",
+                checksum.version
+            )));
+        }
+
+        Ok(checksums)
+    }
+
+    fn deserialize_digest<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let digest = String::deserialize(deserializer)?;
+
+        if digest.len() != 64 || !digest.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(D::Error::custom(format!(
+                "`{}` is not a digest, expected 64 hex digits",
+                digest
+            )));
+        }
+
+        Ok(Some(digest))
+    }
+}
+
+/// A patch in the package's `patches/` directory.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Patch {
+    /// A file name, without separators or `..`, so it can't leave `patches/`.
+    #[serde(deserialize_with = "Patch::deserialize_file")]
+    pub file: String,
+    /// Versions the patch applies to.
+    #[serde(default, skip_serializing_if = "mix_version::Requirement::is_star")]
+    pub version: mix_version::Requirement,
+    /// Leading path components to strip, as in `patch -p`.
+    #[serde(default = "Patch::default_strip")]
+    pub strip: usize,
+}
+
+impl Patch {
+    const fn default_strip() -> usize {
+        1
+    }
+
+    fn deserialize_file<'de, D>(deserializer: D) -> Result<String, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let file = String::deserialize(deserializer)?;
+        let separator = |c| matches!(c, '/' | '\\' | '\0');
+
+        if file.is_empty() || file == "." || file == ".." || file.contains(separator) {
+            return Err(D::Error::custom(format!(
+                "`{}` is not a file name within patches/",
+                file
+            )));
+        }
+
+        Ok(file)
+    }
+}
+
+impl cmp::Ord for Patch {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.file
+            .cmp(&other.file)
+            .then(self.strip.cmp(&other.strip))
+            .then_with(|| self.version.to_string().cmp(&other.version.to_string()))
+    }
+}
+
+impl cmp::PartialOrd for Patch {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// A build recipe.
+///
+/// Each phase command is run with `sh -c` and replaces what the build system would run, an
+/// empty command skips the phase.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Build {
+    /// Detected from the source tree when missing.
+    #[serde(default)]
+    pub system: Option<System>,
+    /// Extra arguments for the build system, passed to configure where it has one.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// Environment variables set for every phase.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Whether to build outside of the source directory.
+    #[serde(default, rename = "build-dir")]
+    pub build_dir: Option<bool>,
+    #[serde(default)]
+    pub prepare: Option<String>,
+    #[serde(default)]
+    pub configure: Option<String>,
+    #[serde(default)]
+    pub compile: Option<String>,
+    #[serde(default)]
+    pub install: Option<String>,
+}
+
+/// A build option, and the argument each build system receives for it.
+///
+/// Build systems without a mapping don't receive the option, custom phase commands see every
+/// option as `OPTION_<NAME>`.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct BuildOption {
+    /// `false` when missing.
+    #[serde(default)]
+    pub default: Value,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub autotools: Option<Autotools>,
+    /// CMake variable, set to `ON`, `OFF` or the value.
+    #[serde(default)]
+    pub cmake: Option<String>,
+    /// Meson option, set to `true`, `false` or the value.
+    #[serde(default)]
+    pub meson: Option<String>,
+}
+
+/// Which pair of configure flags a build option maps to.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Autotools {
+    /// `--enable-name`, `--disable-name` or `--enable-name=value`.
+    Enable(String),
+    /// `--with-name`, `--without-name` or `--with-name=value`.
+    With(String),
+}
+
+/// The value of a build option.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(untagged)]
+pub enum Value {
+    Bool(bool),
+    String(String),
+}
+
+impl Default for Value {
+    fn default() -> Self {
+        Value::Bool(false)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(value) => fmt.write_fmt(format_args!("{}", value)),
+            Value::String(value) => fmt.write_str(value),
+        }
+    }
+}
+
+/// Build systems mix knows how to drive.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum System {
+    Autotools,
+    Cargo,
+    Cmake,
+    Make,
+    Meson,
+}
+
+impl fmt::Display for System {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            System::Autotools => "autotools",
+            System::Cargo => "cargo",
+            System::Cmake => "cmake",
+            System::Make => "make",
+            System::Meson => "meson",
+        };
+
+        fmt.write_str(name)
+    }
+}
+
+/// Dependencies and sources which only apply to targets matching a pattern.
+#[derive(Clone, Debug, Default, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+pub struct Conditional {
     #[serde(default, rename = "depend")]
     pub dependencies: BTreeSet<Requirement>,
+    #[serde(default, rename = "build-depend")]
+    pub build_dependencies: BTreeSet<Requirement>,
+    #[serde(default, rename = "test-depend")]
+    pub test_dependencies: BTreeSet<Requirement>,
+    #[serde(default)]
+    pub bootstrap: BTreeSet<Requirement>,
+    /// Replace the unconditional sources, rather than adding to them.
     #[serde(default, rename = "source")]
     pub sources: BTreeSet<Source>,
 }
 
+impl Manifest {
+    /// Parse a manifest, locating the offending field on failure.
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let deserializer = serde_yaml::Deserializer::from_str(text);
+
+        serde_path_to_error::deserialize(deserializer)
+            .map_err(|error| Error::Invalid(Invalid::new(text, error)))
+    }
+}
+
 impl FromStr for Manifest {
     type Err = Error;
 
     fn from_str(text: &str) -> Result<Self, Self::Err> {
-        let this: Self = serde_yaml::from_str(text)?;
-
-        Ok(this)
+        Self::parse(text)
     }
 }
 
 #[derive(Debug)]
 pub enum Error {
     Io(io::Error),
-    Serde(serde_yaml::Error),
+    Invalid(Invalid),
 }
 
 impl From<io::Error> for Error {
@@ -40,20 +333,11 @@ impl From<io::Error> for Error {
     }
 }
 
-// TODO: Reconstruct error from display of serde_yaml::Error.
-impl From<serde_yaml::Error> for Error {
-    fn from(error: serde_yaml::Error) -> Self {
-        Error::Serde(error)
-    }
-}
-
 impl fmt::Display for Error {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        use Error::*;
-
         match &self {
-            Io(error) => fmt.write_fmt(format_args!("{}", error))?,
-            Serde(error) => fmt.write_fmt(format_args!("{}", error))?,
+            Error::Io(error) => fmt.write_fmt(format_args!("{}", error))?,
+            Error::Invalid(error) => fmt.write_fmt(format_args!("{}", error))?,
         }
 
         Ok(())
@@ -61,77 +345,3 @@ impl fmt::Display for Error {
 }
 
 impl error::Error for Error {}
-
-// TODO: use mix_shell
-pub fn print_error(
-    error: serde_yaml::Error,
-    file_name: &str,
-    manifest: &str,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync + 'static>> {
-    let writer = StandardStream::stderr(ColorChoice::Always);
-    let config = Config {
-        chars: Chars::ascii(),
-        ..Config::default()
-    };
-
-    let file = SimpleFile::new(file_name, &manifest);
-    let start = error.location().unwrap().index();
-    let rest = &manifest[start..];
-    let end = start + rest.find('\n').unwrap_or(rest.len());
-    let message = error.to_string();
-
-    // NOTE: this is utter shit.
-    if message.contains("invalid type: map, expected atom requirement") {
-        let diagnostic = Diagnostic::error()
-            .with_message("failed to parse manifest")
-            .with_labels(vec![
-                Label::primary((), end..end).with_message("invalid atom, wrap it in \"\"")
-            ]);
-
-        term::emit(&mut writer.lock(), &config, &file, &diagnostic)?;
-    } else if message.contains("unexpected character in package id") {
-        let diagnostic = Diagnostic::error()
-            .with_message("failed to parse manifest")
-            .with_labels(vec![
-                Label::primary((), start..end).with_message("unexpected character in package id")
-            ]);
-
-        term::emit(&mut writer.lock(), &config, &file, &diagnostic)?;
-    } else if message.contains("unexpected character in repository id") {
-        let diagnostic = Diagnostic::error()
-            .with_message("failed to parse manifest")
-            .with_labels(vec![
-                Label::primary((), start..end).with_message("unexpected character in package id")
-            ]);
-
-        term::emit(&mut writer.lock(), &config, &file, &diagnostic)?;
-    } else if message.contains("invalid type: map, expected source") {
-        let diagnostic = Diagnostic::error()
-            .with_message("failed to parse manifest")
-            .with_labels(vec![
-                Label::primary((), end..end).with_message("invalid source, wrap it in \"\"")
-            ]);
-
-        term::emit(&mut writer.lock(), &config, &file, &diagnostic)?;
-    } else if message.contains("unknown scheme") {
-        let diagnostic = Diagnostic::error()
-            .with_message("failed to parse manifest")
-            .with_labels(vec![
-                Label::primary((), start..end).with_message("unknown source scheme")
-            ]);
-
-        term::emit(&mut writer.lock(), &config, &file, &diagnostic)?;
-    } else if message.contains("expected user") {
-        let diagnostic = Diagnostic::error()
-            .with_message("failed to parse manifest")
-            .with_labels(vec![
-                Label::primary((), start..end).with_message("expected user in source")
-            ]);
-
-        term::emit(&mut writer.lock(), &config, &file, &diagnostic)?;
-    } else {
-        println!("DEBUG {:?}", &message);
-    }
-
-    Ok(())
-}
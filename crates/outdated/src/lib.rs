@@ -0,0 +1,155 @@
+#![feature(format_args_nl)]
+
+use mix_packages::{Package, Packages};
+use mix_shell::{header, writeln, AsyncWrite};
+use mix_version::Version;
+use path::PathBuf;
+use serde::Serialize;
+use std::sync::Arc;
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub offline: bool,
+    pub json: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    repository: String,
+    package: String,
+    installed: Vec<String>,
+    latest: Option<String>,
+    outdated: bool,
+    allowed: bool,
+    held: Option<String>,
+    held_back: bool,
+    /// Why upstream versions couldn't be refreshed, `latest` is from the cache then.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Report installed packages alongside the newest upstream version, without building anything.
+///
+/// Packages held back by a hold in `settings.yml` are reported as such. Sources which fail to
+/// refresh are reported per package, from whatever is cached.
+pub async fn outdated(
+    config: mix_config::Config,
+    outdated_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let mut rows = Vec::new();
+
+    for package in packages.installed().iter() {
+        let mut error = None;
+
+        if !outdated_config.offline {
+            for source in package.sources().iter() {
+                if let Err(source_error) = source.refresh(&config).await {
+                    error = Some(format!("unable to refresh {}: {}", source, source_error));
+                }
+            }
+        }
+
+        let upstream = package.upstream_versions(&config).await;
+        let latest = upstream.latest().map(|entry| &entry.version);
+        let outdated = match (package.versions().latest(), latest) {
+            (Some(installed), Some(latest)) => latest > installed,
+            _ => false,
+        };
+
+        let allowed = latest.map_or(true, |latest| is_allowed(&packages, package, latest));
+        let hold = config.hold(package.repository_id(), package.package_id());
+        let held_back = match (hold, latest) {
+            (Some(hold), Some(latest)) => !latest.matches(hold),
+            _ => false,
+        };
+
+        rows.push(Row {
+            repository: package.repository_id().to_string(),
+            package: package.package_id().to_string(),
+            installed: package.versions().iter().map(ToString::to_string).collect(),
+            latest: latest.map(ToString::to_string),
+            outdated,
+            allowed,
+            held: hold.map(ToString::to_string),
+            held_back,
+            error,
+        });
+    }
+
+    if outdated_config.json {
+        writeln!(config.shell(), "{}", serde_json::to_string_pretty(&rows)?)?;
+
+        return Ok(());
+    }
+
+    let shell = config.shell();
+    let names = rows
+        .iter()
+        .map(|row| format!("{}/{}", row.repository, row.package))
+        .collect::<Vec<_>>();
+
+    let installed = rows
+        .iter()
+        .map(|row| row.installed.join(", "))
+        .collect::<Vec<_>>();
+
+    let name_width = names.iter().map(String::len).max().unwrap_or(0);
+    let installed_width = installed.iter().map(String::len).max().unwrap_or(0);
+
+    for ((row, name), installed) in rows.iter().zip(names).zip(installed) {
+        let latest = row.latest.as_deref().unwrap_or("-");
+        let held = format!(
+            "outdated, held at {}",
+            row.held.as_deref().unwrap_or_default()
+        );
+        let status = if !row.outdated {
+            shell.theme().output_paint("up to date")
+        } else if row.held_back {
+            shell.theme().warning_paint(held.as_str())
+        } else if row.allowed {
+            shell.theme().command_paint("outdated")
+        } else {
+            shell
+                .theme()
+                .warning_paint("outdated, not allowed by requirements")
+        };
+
+        header!(
+            shell,
+            "{} {} {} {}",
+            shell
+                .theme()
+                .arguments_paint(format!("{:<width$}", name, width = name_width)),
+            format!("{:<width$}", installed, width = installed_width),
+            latest,
+            status
+        )?;
+
+        if let Some(error) = &row.error {
+            header!(
+                shell,
+                "  {} {}",
+                shell.theme().warning_paint("warning"),
+                error
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether every installed dependent's requirement on `package` allows `version`.
+fn is_allowed(packages: &Packages, package: &Package, version: &Version) -> bool {
+    packages.dependents(package).all(|dependent| {
+        dependent
+            .dependencies()
+            .iter()
+            .filter(|requirement| package.is_required_by(requirement))
+            .all(|requirement| version.matches(&requirement.requirement))
+    })
+}
@@ -2,6 +2,7 @@ use futures_util::stream::{BoxStream, StreamExt, TryStreamExt};
 use mix_atom::Atom;
 use mix_config::Config;
 use mix_id::{PackageId, RepositoryId};
+use mix_shell::{header, AsyncWrite};
 use mix_triple::Triple;
 use mix_version::Version;
 use path::{Path, PathBuf};
@@ -28,6 +29,7 @@ mod shared;
 pub struct Packages {
     repository_id: HashMap<RepositoryId, Set>,
     package_id: HashMap<PackageId, Set>,
+    providers: HashMap<PackageId, Set>,
     installed: Set,
     not_installed: Set,
     all: Set,
@@ -38,6 +40,7 @@ impl Packages {
     pub fn new() -> Self {
         let repository_id = HashMap::new();
         let package_id = HashMap::new();
+        let providers = HashMap::new();
         let installed = Set::new();
         let not_installed = Set::new();
         let all = Set::new();
@@ -45,6 +48,7 @@ impl Packages {
         Self {
             repository_id,
             package_id,
+            providers,
             installed,
             not_installed,
             all,
@@ -52,7 +56,9 @@ impl Packages {
     }
 
     /// Load a tree of packages from the config provided.
-    pub async fn from_config(config: &Config) -> io::Result<Self> {
+    ///
+    /// Packages whose manifest fails to load are reported through the shell and left out.
+    pub async fn from_config(config: &Config) -> Result<Self> {
         let mut list: HashMap<(RepositoryId, PackageId), PackageRef> = HashMap::new();
         let _ = config.repos_prefix().create_dir_all_async().await;
         let mut repository_dirs = read_dirs(config.repos_prefix()).await?;
@@ -84,7 +90,11 @@ impl Packages {
 
                 let package = match future.await {
                     Ok(package) => package,
-                    Err(_) => continue,
+                    Err(error) => {
+                        report(config, &package_dir.join("manifest.yml"), &error).await?;
+
+                        continue;
+                    }
                 };
 
                 list.insert((repository_id.clone(), package_id.clone()), package);
@@ -191,6 +201,13 @@ impl Packages {
                 set
             });
 
+        for provides in package.provides() {
+            self.providers
+                .entry(provides.clone())
+                .or_insert_with(Set::new)
+                .insert(package.clone());
+        }
+
         if package.installed() {
             self.installed.insert(package.clone());
         } else {
@@ -241,6 +258,29 @@ impl Packages {
         packages::Packages { iter }
     }
 
+    /// Return a set of packages which provide the virtual package `id`.
+    pub fn providers<Q>(&self, id: &Q) -> Option<&Set>
+    where
+        PackageId: Borrow<Q>,
+        Q: Ord + Hash,
+    {
+        self.providers.get(id)
+    }
+
+    /// Returns a set of packages which provide the virtual package `id`, iterator variant.
+    pub fn providers_iter<'a, Q>(&'a self, id: &Q) -> packages::Packages<'a>
+    where
+        PackageId: Borrow<Q>,
+        Q: Ord + Hash,
+    {
+        let iter = self
+            .providers(id)
+            .into_iter()
+            .flat_map(packages::map_set_to_iter as packages::MapSetToIter<'a>);
+
+        packages::Packages { iter }
+    }
+
     /// Get a package by `repository_id` and `package_id`.
     pub fn get(&self, repository_id: &RepositoryId, package_id: &PackageId) -> Option<&Package> {
         self.repository(repository_id)
@@ -271,6 +311,8 @@ impl Packages {
     }
 
     /// Get matches.
+    ///
+    /// Without a repository, an id no package has falls back to the packages providing it.
     pub fn matches<'a>(&'a self, requirement: &'a mix_atom::Requirement) -> matches::Matches<'a> {
         let iter = if let Some(repository_id) = &requirement.repository_id {
             let iter = self
@@ -278,9 +320,13 @@ impl Packages {
                 .into_iter();
 
             atoms::Atoms::Exact(iter)
-        } else {
+        } else if self.packages(&requirement.package_id).is_some() {
             let iter = self.packages_iter(&requirement.package_id);
 
+            atoms::Atoms::Set(iter)
+        } else {
+            let iter = self.providers_iter(&requirement.package_id);
+
             atoms::Atoms::Set(iter)
         };
 
@@ -303,6 +349,52 @@ impl Packages {
             .flat_map(|(_package_id, shared)| shared.iter())
     }
 
+    /// Installed packages which depend on `package` at runtime.
+    ///
+    /// Build and test dependencies aren't needed once a package is installed.
+    pub fn dependents<'a>(&'a self, package: &'a Package) -> impl Iterator<Item = &'a Package> {
+        self.installed.iter().filter(move |dependent| {
+            dependent != &package
+                && dependent
+                    .dependencies()
+                    .iter()
+                    .any(|requirement| package.is_required_by(requirement))
+        })
+    }
+
+    /// Installed packages which depend on `package` at runtime, and which no version of it but
+    /// those in `removed` satisfies.
+    pub fn broken_dependents<'a>(
+        &'a self,
+        package: &'a Package,
+        removed: &'a [Version],
+    ) -> impl Iterator<Item = &'a Package> {
+        self.dependents(package).filter(move |dependent| {
+            dependent
+                .dependencies()
+                .iter()
+                .filter(|requirement| package.is_required_by(requirement))
+                .any(|requirement| {
+                    !package
+                        .versions()
+                        .iter()
+                        .filter(|version| !removed.contains(version))
+                        .any(|version| version.matches(&requirement.requirement))
+                })
+        })
+    }
+
+    /// These packages, with the manifest blocks matching `target` applied.
+    pub fn for_target(&self, target: Triple) -> Self {
+        let mut packages = Self::new();
+
+        for package in self.iter() {
+            packages.insert(package.for_target(target));
+        }
+
+        packages
+    }
+
     /// An iterator over all packages.
     pub fn iter(&self) -> set::Iter<'_> {
         self.all.iter()
@@ -322,6 +414,12 @@ impl Packages {
                 set.remove(id);
             }
 
+            for provides in package.provides() {
+                if let Some(set) = self.providers.get_mut(provides) {
+                    set.remove(id);
+                }
+            }
+
             if package.installed() {
                 self.installed.remove(id);
             } else {
@@ -347,7 +445,7 @@ impl Packages {
 
     /// List of packages that are not installed.
     pub fn not_installed(&self) -> &Set {
-        &self.installed
+        &self.not_installed
     }
 }
 
@@ -357,6 +455,42 @@ impl fmt::Debug for Packages {
     }
 }
 
+/// Report why the manifest at `path` failed to load.
+///
+/// Directories without a manifest aren't packages, so they're skipped quietly.
+pub async fn report(config: &Config, path: &Path, error: &Error) -> Result<()> {
+    let shell = config.shell();
+
+    match error.downcast_ref::<mix_manifest::Error>() {
+        Some(mix_manifest::Error::Invalid(invalid)) => {
+            shell
+                .emit(
+                    path.as_str(),
+                    &invalid.text,
+                    &invalid.diagnostic("manifest"),
+                )
+                .await?;
+        }
+        _ => {
+            let missing = error
+                .downcast_ref::<io::Error>()
+                .map_or(false, |error| error.kind() == io::ErrorKind::NotFound);
+
+            if !missing {
+                header!(
+                    shell,
+                    "{} {}: {}",
+                    shell.theme().warning_paint("skipped"),
+                    path,
+                    error
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 pub async fn read_dirs<'a>(
     path: impl AsRef<Path>,
 ) -> io::Result<BoxStream<'a, io::Result<PathBuf>>> {
@@ -369,3 +503,134 @@ pub async fn read_dirs<'a>(
 
     Ok(stream)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Package, PackageRef, Packages};
+    use mix_atom::Requirement;
+    use mix_id::{PackageId, RepositoryId};
+    use mix_manifest::Build;
+    use mix_source::Sources;
+    use mix_version::{Version, Versions};
+    use path::Path;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    /// `core/<name>` installed at `versions`, depending on `dependencies`.
+    fn package(name: &str, versions: &[&str], dependencies: &[&str]) -> PackageRef {
+        let mut installed = Versions::new();
+
+        for version in versions {
+            installed.insert(
+                Version::parse(version).unwrap(),
+                Path::new("/prefix/core").join(name).join(version),
+            );
+        }
+
+        PackageRef {
+            repository_id: RepositoryId::CORE.clone(),
+            package_id: PackageId::try_from(name).unwrap(),
+            versions: installed,
+            description: None,
+            homepage: None,
+            license: None,
+            maintainers: Vec::new(),
+            keywords: BTreeSet::new(),
+            dependencies: dependencies
+                .iter()
+                .map(|requirement| Requirement::parse(requirement).unwrap())
+                .collect(),
+            build_dependencies: BTreeSet::new(),
+            test_dependencies: BTreeSet::new(),
+            bootstrap: BTreeSet::new(),
+            sources: Sources::new("/cache"),
+            provides: BTreeSet::new(),
+            conflicts: BTreeSet::new(),
+            replaces: BTreeSet::new(),
+            targets: BTreeMap::new(),
+            build: Build::default(),
+            options: BTreeMap::new(),
+            patches: None,
+            checksums: Vec::new(),
+            manifest_path: Path::new("/repos/core").join(name).join("manifest.yml"),
+            patches_dir: Path::new("/repos/core").join(name).join("patches"),
+            build_prefix: Path::new("/build/core").join(name),
+        }
+    }
+
+    fn packages(list: Vec<PackageRef>) -> Packages {
+        let mut packages = Packages::new();
+
+        for package in list {
+            packages.insert(package);
+        }
+
+        packages
+    }
+
+    fn get<'a>(packages: &'a Packages, name: &str) -> &'a Package {
+        packages
+            .get(RepositoryId::CORE, &PackageId::try_from(name).unwrap())
+            .unwrap()
+    }
+
+    fn names<'a>(dependents: impl Iterator<Item = &'a Package>) -> Vec<&'a str> {
+        dependents
+            .map(|package| package.package_id().as_str())
+            .collect()
+    }
+
+    fn versions(versions: &[&str]) -> Vec<Version> {
+        versions
+            .iter()
+            .map(|version| Version::parse(version).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn dependents_ignore_build_dependencies() {
+        let mut tool = package("tool", &["1.0.0"], &[]);
+
+        tool.build_dependencies = [Requirement::parse("zlib").unwrap()].into_iter().collect();
+
+        let packages = packages(vec![
+            package("zlib", &["1.2.0"], &[]),
+            package("curl", &["7.0.0"], &["zlib"]),
+            tool,
+        ]);
+
+        assert_eq!(names(packages.dependents(get(&packages, "zlib"))), ["curl"]);
+    }
+
+    #[test]
+    fn removing_the_only_version_breaks_dependents() {
+        let packages = packages(vec![
+            package("zlib", &["1.2.0"], &[]),
+            package("curl", &["7.0.0"], &["zlib"]),
+        ]);
+
+        let zlib = get(&packages, "zlib");
+        let removed = versions(&["1.2.0"]);
+
+        assert_eq!(names(packages.broken_dependents(zlib, &removed)), ["curl"]);
+    }
+
+    #[test]
+    fn remaining_version_keeps_dependents() {
+        let packages = packages(vec![
+            package("zlib", &["1.2.0", "1.3.0"], &[]),
+            package("curl", &["7.0.0"], &["zlib:>=1.2"]),
+            package("png", &["1.6.0"], &["zlib:<1.3"]),
+        ]);
+
+        let zlib = get(&packages, "zlib");
+
+        // Only `png` needs 1.2.0, 1.3.0 still satisfies `curl`.
+        let removed = versions(&["1.2.0"]);
+
+        assert_eq!(names(packages.broken_dependents(zlib, &removed)), ["png"]);
+
+        let removed = versions(&["1.3.0"]);
+
+        assert!(packages.broken_dependents(zlib, &removed).next().is_none());
+    }
+}
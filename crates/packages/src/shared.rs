@@ -2,17 +2,20 @@ use crate::Result;
 use mix_atom::Requirement;
 use mix_config::Config;
 use mix_id::{PackageId, RepositoryId};
-use mix_manifest::Manifest;
+use mix_manifest::{Build, BuildOption, Checksum, Conditional, Manifest, Patch};
+use mix_shell::{async_trait, write, AsyncDisplay, Shell};
+use mix_source::versions::Entry;
 use mix_source::Sources;
+use mix_triple::Triple;
 use mix_version::Versions;
 use path::{Path, PathBuf};
 use std::borrow::Borrow;
 use std::cmp::Ord;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::str::FromStr;
 use std::sync::Arc;
 
-#[derive(Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct PackageRef {
     /// Ditto.
     pub repository_id: RepositoryId,
@@ -23,15 +26,66 @@ pub struct PackageRef {
     /// Installed versions.
     pub versions: Versions,
 
-    /// Packages this package depends on.
+    /// One line summary.
+    pub description: Option<String>,
+
+    /// Ditto.
+    pub homepage: Option<String>,
+
+    /// SPDX license expression.
+    pub license: Option<String>,
+
+    /// Ditto.
+    pub maintainers: Vec<String>,
+
+    /// Extra words search matches.
+    pub keywords: BTreeSet<String>,
+
+    /// Packages this package depends on at runtime.
     pub dependencies: BTreeSet<Requirement>,
 
+    /// Packages only needed while building this package.
+    pub build_dependencies: BTreeSet<Requirement>,
+
+    /// Packages only needed to test this package.
+    pub test_dependencies: BTreeSet<Requirement>,
+
+    /// Packages which must already be installed to build this package.
+    pub bootstrap: BTreeSet<Requirement>,
+
     /// Sources which provide this package.
     pub sources: Sources,
 
+    /// Virtual packages this package provides.
+    pub provides: BTreeSet<PackageId>,
+
+    /// Packages which can't be installed alongside this package.
+    pub conflicts: BTreeSet<Requirement>,
+
+    /// Packages this package supersedes.
+    pub replaces: BTreeSet<Requirement>,
+
+    /// Dependencies and sources keyed by triple pattern.
+    pub targets: BTreeMap<String, Conditional>,
+
+    /// How to build this package.
+    pub build: Build,
+
+    /// Build options by name.
+    pub options: BTreeMap<String, BuildOption>,
+
+    /// Patches to apply, in order, or `None` to apply everything in `patches_dir`.
+    pub patches: Option<Vec<Patch>>,
+
+    /// Digests of release tarballs.
+    pub checksums: Vec<Checksum>,
+
     /// Cached manifest path.
     pub manifest_path: PathBuf,
 
+    /// Cached patches directory.
+    pub patches_dir: PathBuf,
+
     /// Cached build prefix.
     pub build_prefix: PathBuf,
 }
@@ -53,6 +107,12 @@ impl PackageRef {
             .join(package_id.as_str())
             .join("manifest.yml");
 
+        let patches_dir = config
+            .repos_prefix()
+            .join(repository_id.as_str())
+            .join(package_id.as_str())
+            .join("patches");
+
         let manifest_string = manifest_path.read_to_string_async().await?;
         let manifest = Manifest::from_str(manifest_string.as_str())?;
         let mut sources = Sources::new(config.cache_prefix());
@@ -66,8 +126,25 @@ impl PackageRef {
             package_id,
             versions: Versions::new(),
             sources,
+            description: manifest.description,
+            homepage: manifest.homepage,
+            license: manifest.license,
+            maintainers: manifest.maintainers,
+            keywords: manifest.keywords,
             dependencies: manifest.dependencies,
+            build_dependencies: manifest.build_dependencies,
+            test_dependencies: manifest.test_dependencies,
+            bootstrap: manifest.bootstrap,
+            provides: manifest.provides,
+            conflicts: manifest.conflicts,
+            replaces: manifest.replaces,
+            targets: manifest.targets,
+            build: manifest.build,
+            options: manifest.options,
+            patches: manifest.patches,
+            checksums: manifest.checksums,
             manifest_path,
+            patches_dir,
             build_prefix,
         })
     }
@@ -88,6 +165,12 @@ impl PackageRef {
             .join(package_id.as_str())
             .join("manifest.yml");
 
+        let patches_dir = config
+            .repos_prefix()
+            .join(repository_id.as_str())
+            .join(package_id.as_str())
+            .join("patches");
+
         let sources = Sources::new(config.cache_prefix());
 
         Self {
@@ -95,8 +178,25 @@ impl PackageRef {
             package_id,
             versions: Versions::new(),
             sources,
+            description: None,
+            homepage: None,
+            license: None,
+            maintainers: Vec::new(),
+            keywords: BTreeSet::new(),
             dependencies: BTreeSet::new(),
+            build_dependencies: BTreeSet::new(),
+            test_dependencies: BTreeSet::new(),
+            bootstrap: BTreeSet::new(),
+            provides: BTreeSet::new(),
+            conflicts: BTreeSet::new(),
+            replaces: BTreeSet::new(),
+            targets: BTreeMap::new(),
+            build: Build::default(),
+            options: BTreeMap::new(),
+            patches: None,
+            checksums: Vec::new(),
             manifest_path,
+            patches_dir,
             build_prefix,
         }
     }
@@ -109,10 +209,70 @@ impl PackageRef {
         &self.package_id
     }
 
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub fn homepage(&self) -> Option<&str> {
+        self.homepage.as_deref()
+    }
+
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+
+    pub fn maintainers(&self) -> &[String] {
+        self.maintainers.as_slice()
+    }
+
+    pub fn keywords(&self) -> &BTreeSet<String> {
+        &self.keywords
+    }
+
     pub fn dependencies(&self) -> &BTreeSet<Requirement> {
         &self.dependencies
     }
 
+    pub fn build_dependencies(&self) -> &BTreeSet<Requirement> {
+        &self.build_dependencies
+    }
+
+    pub fn test_dependencies(&self) -> &BTreeSet<Requirement> {
+        &self.test_dependencies
+    }
+
+    pub fn bootstrap(&self) -> &BTreeSet<Requirement> {
+        &self.bootstrap
+    }
+
+    pub fn provides(&self) -> &BTreeSet<PackageId> {
+        &self.provides
+    }
+
+    pub fn conflicts(&self) -> &BTreeSet<Requirement> {
+        &self.conflicts
+    }
+
+    pub fn replaces(&self) -> &BTreeSet<Requirement> {
+        &self.replaces
+    }
+
+    pub fn build(&self) -> &Build {
+        &self.build
+    }
+
+    pub fn options(&self) -> &BTreeMap<String, BuildOption> {
+        &self.options
+    }
+
+    pub fn patches(&self) -> Option<&[Patch]> {
+        self.patches.as_deref()
+    }
+
+    pub fn checksums(&self) -> &[Checksum] {
+        self.checksums.as_slice()
+    }
+
     pub fn get_dependency<Q>(&self, atom: &Q) -> Option<&Requirement>
     where
         Requirement: Borrow<Q>,
@@ -152,9 +312,60 @@ impl PackageRef {
     pub fn manifest_path(&self) -> &Path {
         self.manifest_path.as_path()
     }
+
+    pub fn patches_dir(&self) -> &Path {
+        self.patches_dir.as_path()
+    }
+
     pub fn build_prefix(&self) -> &Path {
         self.build_prefix.as_path()
     }
+
+    /// This package, with the blocks matching `target` merged in.
+    ///
+    /// Conditional requirements replace unconditional ones on the same package, and conditional
+    /// sources replace every unconditional source, so a release published by both resolves to
+    /// the conditional one.
+    pub fn for_target(&self, target: Triple) -> Self {
+        let mut package = self.clone();
+        let conditionals = self
+            .targets
+            .iter()
+            .filter(|(pattern, _conditional)| target.matches(pattern))
+            .map(|(_pattern, conditional)| conditional)
+            .collect::<Vec<_>>();
+
+        if conditionals
+            .iter()
+            .any(|conditional| !conditional.sources.is_empty())
+        {
+            package.sources.clear();
+        }
+
+        for conditional in conditionals {
+            for requirement in conditional.dependencies.iter() {
+                package.dependencies.replace(requirement.clone());
+            }
+
+            for requirement in conditional.build_dependencies.iter() {
+                package.build_dependencies.replace(requirement.clone());
+            }
+
+            for requirement in conditional.test_dependencies.iter() {
+                package.test_dependencies.replace(requirement.clone());
+            }
+
+            for requirement in conditional.bootstrap.iter() {
+                package.bootstrap.replace(requirement.clone());
+            }
+
+            for source in conditional.sources.iter() {
+                package.sources.insert(source.clone());
+            }
+        }
+
+        package
+    }
 }
 
 impl Borrow<RepositoryId> for PackageRef {
@@ -191,10 +402,97 @@ impl Package {
         self.0.package_id()
     }
 
+    pub fn description(&self) -> Option<&str> {
+        self.0.description()
+    }
+
+    pub fn homepage(&self) -> Option<&str> {
+        self.0.homepage()
+    }
+
+    pub fn license(&self) -> Option<&str> {
+        self.0.license()
+    }
+
+    pub fn maintainers(&self) -> &[String] {
+        self.0.maintainers()
+    }
+
+    pub fn keywords(&self) -> &BTreeSet<String> {
+        self.0.keywords()
+    }
+
     pub fn dependencies(&self) -> &BTreeSet<Requirement> {
         self.0.dependencies()
     }
 
+    pub fn build_dependencies(&self) -> &BTreeSet<Requirement> {
+        self.0.build_dependencies()
+    }
+
+    pub fn test_dependencies(&self) -> &BTreeSet<Requirement> {
+        self.0.test_dependencies()
+    }
+
+    pub fn bootstrap(&self) -> &BTreeSet<Requirement> {
+        self.0.bootstrap()
+    }
+
+    pub fn provides(&self) -> &BTreeSet<PackageId> {
+        self.0.provides()
+    }
+
+    pub fn conflicts(&self) -> &BTreeSet<Requirement> {
+        self.0.conflicts()
+    }
+
+    pub fn replaces(&self) -> &BTreeSet<Requirement> {
+        self.0.replaces()
+    }
+
+    pub fn build(&self) -> &Build {
+        self.0.build()
+    }
+
+    pub fn options(&self) -> &BTreeMap<String, BuildOption> {
+        self.0.options()
+    }
+
+    pub fn patches(&self) -> Option<&[Patch]> {
+        self.0.patches()
+    }
+
+    pub fn checksums(&self) -> &[Checksum] {
+        self.0.checksums()
+    }
+
+    /// Digests the tarball of `entry` has to match.
+    pub fn checksums_for(&self, entry: &Entry) -> Vec<mix_config::checksum::Checksum> {
+        self.checksums()
+            .iter()
+            .filter(|checksum| checksum.version == entry.version)
+            .filter(|checksum| {
+                checksum
+                    .source
+                    .as_ref()
+                    .map_or(true, |source| source == &entry.source)
+            })
+            .flat_map(|checksum| {
+                let sha256 = checksum
+                    .sha256
+                    .clone()
+                    .map(mix_config::checksum::Checksum::Sha256);
+
+                let blake3 = checksum
+                    .blake3
+                    .clone()
+                    .map(mix_config::checksum::Checksum::Blake3);
+
+                sha256.into_iter().chain(blake3)
+            })
+            .collect()
+    }
+
     pub fn get_dependency<Q>(&self, atom: &Q) -> Option<&Requirement>
     where
         Requirement: Borrow<Q>,
@@ -219,6 +517,14 @@ impl Package {
         self.0.versions()
     }
 
+    pub fn manifest_path(&self) -> &Path {
+        self.0.manifest_path()
+    }
+
+    pub fn patches_dir(&self) -> &Path {
+        self.0.patches_dir()
+    }
+
     pub fn build_prefix(&self) -> &Path {
         self.0.build_prefix()
     }
@@ -226,6 +532,52 @@ impl Package {
     pub fn installed(&self) -> bool {
         self.0.installed()
     }
+
+    /// This package, with the blocks matching `target` merged in.
+    pub fn for_target(&self, target: Triple) -> Self {
+        Self(Arc::new(self.0.for_target(target)))
+    }
+
+    /// Versions available upstream, read from the cached tags of every source.
+    ///
+    /// Sources which have not been fetched yet are skipped.
+    pub async fn upstream_versions(&self, config: &Config) -> mix_source::versions::Versions {
+        let mut versions = mix_source::versions::Versions::new();
+
+        for source in self.sources().iter() {
+            if let Ok(source_versions) = source.versions(config).await {
+                for entry in source_versions.iter() {
+                    versions.insert(entry.clone());
+                }
+            }
+        }
+
+        versions
+    }
+
+    /// Whether `requirement` refers to this package, or a virtual package it provides, ignoring
+    /// the version requirement.
+    pub fn is_required_by(&self, requirement: &Requirement) -> bool {
+        (requirement.package_id == *self.package_id()
+            || self.provides().contains(&requirement.package_id))
+            && requirement
+                .repository_id
+                .as_ref()
+                .map_or(true, |repository_id| repository_id == self.repository_id())
+    }
+}
+
+#[async_trait(?Send)]
+impl AsyncDisplay<Shell> for Package {
+    async fn fmt(&self, fmt: &Shell) -> mix_shell::Result<()> {
+        write!(
+            fmt,
+            "{}{}{}",
+            fmt.theme().arguments_paint(self.repository_id()),
+            fmt.theme().seperator_paint('/'),
+            fmt.theme().arguments_paint(self.package_id()),
+        )
+    }
 }
 
 impl From<PackageRef> for Package {
@@ -251,3 +603,103 @@ impl Borrow<PackageId> for Package {
         self.package_id()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::PackageRef;
+    use mix_id::{PackageId, RepositoryId};
+    use mix_manifest::{Build, Conditional};
+    use mix_source::{Source, Sources};
+    use mix_triple::Triple;
+    use mix_version::Versions;
+    use path::Path;
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::str::FromStr;
+
+    fn package(sources: &[Source], targets: BTreeMap<String, Conditional>) -> PackageRef {
+        let mut package_sources = Sources::new("/cache");
+
+        for source in sources {
+            package_sources.insert(source.clone());
+        }
+
+        PackageRef {
+            repository_id: RepositoryId::CORE.clone(),
+            package_id: PackageId::try_from("zlib").unwrap(),
+            versions: Versions::new(),
+            description: None,
+            homepage: None,
+            license: None,
+            maintainers: Vec::new(),
+            keywords: BTreeSet::new(),
+            dependencies: BTreeSet::new(),
+            build_dependencies: BTreeSet::new(),
+            test_dependencies: BTreeSet::new(),
+            bootstrap: BTreeSet::new(),
+            sources: package_sources,
+            provides: BTreeSet::new(),
+            conflicts: BTreeSet::new(),
+            replaces: BTreeSet::new(),
+            targets,
+            build: Build::default(),
+            options: BTreeMap::new(),
+            patches: None,
+            checksums: Vec::new(),
+            manifest_path: Path::new("/repos/core/zlib").join("manifest.yml"),
+            patches_dir: Path::new("/repos/core/zlib").join("patches"),
+            build_prefix: Path::new("/build/core").join("zlib"),
+        }
+    }
+
+    fn sources(package: &PackageRef) -> Vec<Source> {
+        package.sources().iter().cloned().collect()
+    }
+
+    /// Both sources publish the same releases, so only the override may remain.
+    fn overridden() -> PackageRef {
+        let conditional = Conditional {
+            sources: [Source::github("madler", "zlib-musl")]
+                .into_iter()
+                .collect(),
+            ..Conditional::default()
+        };
+
+        package(
+            &[Source::github("madler", "zlib")],
+            [("*-linux-musl".to_string(), conditional)]
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn conditional_sources_replace_base_sources() {
+        let package = overridden().for_target(Triple::from_str("x86_64-linux-musl").unwrap());
+
+        assert_eq!(
+            sources(&package),
+            vec![Source::github("madler", "zlib-musl")]
+        );
+    }
+
+    #[test]
+    fn base_sources_without_matching_block() {
+        let package = overridden().for_target(Triple::from_str("x86_64-linux-gnu").unwrap());
+
+        assert_eq!(sources(&package), vec![Source::github("madler", "zlib")]);
+    }
+
+    #[test]
+    fn base_sources_when_block_declares_none() {
+        let conditional = Conditional::default();
+        let package = package(
+            &[Source::github("madler", "zlib")],
+            [("*-linux-musl".to_string(), conditional)]
+                .into_iter()
+                .collect(),
+        )
+        .for_target(Triple::from_str("x86_64-linux-musl").unwrap());
+
+        assert_eq!(sources(&package), vec![Source::github("madler", "zlib")]);
+    }
+}
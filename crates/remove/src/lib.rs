@@ -0,0 +1,169 @@
+#![feature(format_args_nl)]
+
+use mix_atom::Requirement;
+use mix_packages::{Package, Packages};
+use mix_shell::{header, AsyncWrite};
+use mix_triple::Triple;
+use mix_version::Version;
+use path::PathBuf;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub target: Triple,
+    pub requirement: Requirement,
+    pub cascade: bool,
+}
+
+/// Remove installed versions of a package.
+pub async fn remove(
+    config: mix_config::Config,
+    remove_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let packages = packages.for_target(remove_config.target);
+    let matches = packages
+        .matches(&remove_config.requirement)
+        .filter(|package| package.installed())
+        .collect::<Vec<_>>();
+
+    let package = match matches.as_slice() {
+        [] => return Err(format!("{} is not installed", &remove_config.requirement).into()),
+        [package] => *package,
+        _ => {
+            let candidates = matches
+                .iter()
+                .map(|package| format!("{}/{}", package.repository_id(), package.package_id()))
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            return Err(format!(
+                "{} is ambiguous, candidates are: {}",
+                &remove_config.requirement, candidates
+            )
+            .into());
+        }
+    };
+
+    let versions = package
+        .versions()
+        .matches(&remove_config.requirement.requirement)
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if versions.is_empty() {
+        return Err(format!("{} is not installed", &remove_config.requirement).into());
+    }
+
+    let mut removals = vec![(package, versions.clone())];
+
+    let mut dependents = Vec::new();
+    let mut exists = BTreeSet::new();
+
+    exists.insert(package);
+
+    // Dependents only break if no version left behind satisfies them.
+    for dependent in packages.broken_dependents(package, &versions) {
+        if exists.insert(dependent) {
+            dependents.push(dependent);
+            collect_dependents(&packages, dependent, &mut dependents, &mut exists);
+        }
+    }
+
+    if !dependents.is_empty() && !remove_config.cascade {
+        let dependents = dependents
+            .iter()
+            .map(|dependent| format!("{}/{}", dependent.repository_id(), dependent.package_id()))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        return Err(format!(
+            "{}/{} is required by {} (use --cascade to remove them too)",
+            package.repository_id(),
+            package.package_id(),
+            dependents
+        )
+        .into());
+    }
+
+    for dependent in dependents {
+        removals.push((dependent, dependent.versions().iter().cloned().collect()));
+    }
+
+    // Remove the outermost dependents first.
+    for (package, versions) in removals.into_iter().rev() {
+        for version in versions {
+            remove_version(&config, &remove_config, package, &version).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect installed packages that transitively depend on `package`.
+fn collect_dependents<'a>(
+    packages: &'a Packages,
+    package: &'a Package,
+    dependents: &mut Vec<&'a Package>,
+    exists: &mut BTreeSet<&'a Package>,
+) {
+    for dependent in packages.dependents(package) {
+        if exists.contains(dependent) {
+            continue;
+        }
+
+        exists.insert(dependent);
+        dependents.push(dependent);
+
+        collect_dependents(packages, dependent, dependents, exists);
+    }
+}
+
+/// Remove `<prefix>/<triple>/<repository>/<package>/<version>`, the tree `mix_build` installs into.
+async fn remove_version(
+    config: &mix_config::Config,
+    remove_config: &Config,
+    package: &Package,
+    version: &Version,
+) -> Result<()> {
+    let package_dir = remove_config
+        .prefix
+        .join(remove_config.target.as_str())
+        .join(package.repository_id().as_str())
+        .join(package.package_id().as_str());
+
+    let destination = package_dir.join(version.to_string());
+
+    if !destination.exists_async().await {
+        return Ok(());
+    }
+
+    header!(
+        config.shell(),
+        "removing {}/{}:{}",
+        package.repository_id(),
+        package.package_id(),
+        version,
+    )?;
+
+    header!(
+        config.shell(),
+        "{} {}",
+        config.shell().theme().command_paint("destination"),
+        &destination
+    )?;
+
+    config.shell().flush().await?;
+
+    tokio::fs::remove_dir_all(&destination).await?;
+
+    // Clean up the package directory once the last version is gone.
+    let _ = tokio::fs::remove_dir(&package_dir).await;
+
+    Ok(())
+}
@@ -0,0 +1,101 @@
+#![feature(format_args_nl)]
+
+use mix_config::settings::Settings;
+use mix_id::RepositoryId;
+use mix_shell::{header, write, writeln, AsyncDisplay, AsyncWrite};
+use path::PathBuf;
+use std::iter;
+use url::Url;
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub enum Action {
+    /// Add a repository, optionally syncing it straight away.
+    Add { id: String, url: Url, sync: bool },
+
+    /// Remove a repository.
+    Remove { id: String },
+
+    /// List repositories.
+    List,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub action: Action,
+}
+
+/// Manage the repositories in `settings.yml`.
+pub async fn repo(config: mix_config::Config, repo_config: Config) -> Result<()> {
+    let shell = config.shell();
+    let mut settings = Settings::load(config.settings_path()).await?;
+
+    match repo_config.action {
+        Action::Add { id, url, sync } => {
+            let id = RepositoryId::new(id.into_boxed_str())?;
+
+            match settings.repositories.get(&id) {
+                Some(existing) if existing == &url => {}
+                Some(existing) => {
+                    return Err(format!("repository {} already exists ({})", id, existing).into());
+                }
+                None => {
+                    settings.repositories.insert(id.clone(), url.clone());
+                    settings.save(config.settings_path()).await?;
+
+                    header!(
+                        shell,
+                        "{} {} {}",
+                        shell.theme().command_paint("added"),
+                        shell.theme().arguments_paint(&id),
+                        shell.theme().url_paint(&url)
+                    )?;
+                }
+            }
+
+            if sync {
+                // Reload so the new repository is known to sync.
+                let config = mix_config::Config::new(config.prefix()).await?;
+                let sync_config = mix_sync::Config {
+                    prefix: config.prefix().to_path_buf(),
+                    repositories: iter::once(id).collect(),
+                };
+
+                mix_sync::sync(config.clone(), sync_config).await?;
+                config.shell().flush().await?;
+            }
+        }
+        Action::Remove { id } => {
+            if settings.repositories.remove(id.as_str()).is_none() {
+                return Err(format!("unknown repository {}", id).into());
+            }
+
+            settings.priority.retain(|priority| priority.as_str() != id);
+            settings.save(config.settings_path()).await?;
+
+            header!(
+                shell,
+                "{} {}",
+                shell.theme().command_paint("removed"),
+                shell.theme().arguments_paint(&id)
+            )?;
+        }
+        Action::List => {
+            for (id, url) in settings.repositories.iter() {
+                write!(
+                    shell,
+                    "{}{} ",
+                    shell.theme().header_prefix(),
+                    shell.theme().arguments_paint(id)
+                )?;
+                AsyncDisplay::fmt(url, shell).await?;
+                writeln!(shell)?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+#![feature(format_args_nl)]
+
+use mix_id::RepositoryId;
+use mix_packages::{Package, Packages};
+use mix_shell::{write, writeln, AsyncDisplay, AsyncWrite};
+use path::PathBuf;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::Arc;
+
+pub(crate) type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(Debug)]
+pub struct Config {
+    pub prefix: PathBuf,
+    pub pattern: String,
+    pub repository: Option<RepositoryId>,
+    pub json: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct Match {
+    repository: String,
+    package: String,
+    description: Option<String>,
+    license: Option<String>,
+    installed: Vec<String>,
+    available: Vec<String>,
+}
+
+/// Search packages by package id, repository id, sources, description and keywords.
+pub async fn search(
+    config: mix_config::Config,
+    search_config: Config,
+    packages: Arc<Packages>,
+) -> Result<()> {
+    let regex = Regex::new(&search_config.pattern)?;
+    let sources = packages.iter().filter(|package| {
+        package
+            .sources()
+            .iter()
+            .any(|source| regex.is_match(&source.to_string()))
+    });
+
+    let metadata = packages.iter().filter(|package| {
+        package
+            .description()
+            .map_or(false, |description| regex.is_match(description))
+            || package
+                .keywords()
+                .iter()
+                .any(|keyword| regex.is_match(keyword))
+    });
+
+    let matches = packages
+        .matches_package(&regex)
+        .chain(packages.matches_repository(&regex))
+        .chain(sources)
+        .chain(metadata)
+        .filter(|package| {
+            search_config
+                .repository
+                .as_ref()
+                .map_or(true, |repository_id| {
+                    repository_id == package.repository_id()
+                })
+        })
+        .collect::<BTreeSet<&Package>>();
+
+    if search_config.json {
+        let mut results = Vec::new();
+
+        for package in matches {
+            let available = package.upstream_versions(&config).await;
+
+            results.push(Match {
+                repository: package.repository_id().to_string(),
+                package: package.package_id().to_string(),
+                description: package.description().map(ToString::to_string),
+                license: package.license().map(ToString::to_string),
+                installed: package.versions().iter().map(ToString::to_string).collect(),
+                available: available
+                    .iter()
+                    .map(|entry| entry.version.to_string())
+                    .collect(),
+            });
+        }
+
+        writeln!(
+            config.shell(),
+            "{}",
+            serde_json::to_string_pretty(&results)?
+        )?;
+
+        return Ok(());
+    }
+
+    let shell = config.shell();
+
+    for package in matches {
+        let available = package.upstream_versions(&config).await;
+
+        write!(shell, "{}", shell.theme().header_prefix())?;
+        AsyncDisplay::fmt(package, shell).await?;
+
+        for version in package.versions().iter() {
+            write!(shell, " ")?;
+            AsyncDisplay::fmt(version, shell).await?;
+        }
+
+        if let Some(latest) = available.latest() {
+            write!(shell, " {}", shell.theme().seperator_paint("(latest "))?;
+            AsyncDisplay::fmt(&latest.version, shell).await?;
+            write!(shell, "{}", shell.theme().seperator_paint(")"))?;
+        }
+
+        writeln!(shell)?;
+
+        if let Some(description) = package.description() {
+            writeln!(shell, "{}  {}", shell.theme().header_prefix(), description)?;
+        }
+    }
+
+    Ok(())
+}
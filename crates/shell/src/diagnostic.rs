@@ -0,0 +1,33 @@
+use crate::{Result, Shell};
+use codespan_reporting::diagnostic::Diagnostic;
+use codespan_reporting::files::SimpleFile;
+use codespan_reporting::term;
+use codespan_reporting::term::termcolor::Buffer;
+use codespan_reporting::term::{Chars, Config};
+use yansi::Paint;
+
+impl Shell {
+    /// Render `diagnostic` against the contents of `file_name` to stderr.
+    pub async fn emit(&self, file_name: &str, source: &str, diagnostic: &Diagnostic<()>) -> Result {
+        let file = SimpleFile::new(file_name, source);
+        let config = Config {
+            chars: Chars::ascii(),
+            ..Config::default()
+        };
+
+        let mut buffer = if Paint::is_enabled() {
+            Buffer::ansi()
+        } else {
+            Buffer::no_color()
+        };
+
+        term::emit(&mut buffer, &config, &file, diagnostic)?;
+
+        // Keep anything already written to stdout ahead of the diagnostic.
+        self.stdout_flush().await?;
+        self.stderr_write(buffer.as_slice()).await?;
+        self.stderr_flush().await?;
+
+        Ok(())
+    }
+}
@@ -5,7 +5,9 @@ use core::fmt::Arguments;
 pub use crate::shell::Shell;
 pub use crate::theme::Theme;
 pub use async_trait::async_trait;
+pub use codespan_reporting::diagnostic::{Diagnostic, Label};
 
+mod diagnostic;
 mod shell;
 mod theme;
 
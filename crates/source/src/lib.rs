@@ -138,7 +138,17 @@ impl Source {
         }
     }
 
+    /// Fetch upstream tags, unless they are already cached.
     pub async fn update(&self, config: &mix_config::Config) -> Result<()> {
+        self.update_tags(config, false).await
+    }
+
+    /// Fetch upstream tags, replacing any cached tags.
+    pub async fn refresh(&self, config: &mix_config::Config) -> Result<()> {
+        self.update_tags(config, true).await
+    }
+
+    async fn update_tags(&self, config: &mix_config::Config, refresh: bool) -> Result<()> {
         match self.kind() {
             Kind::Github => unsafe {
                 let url = format!(
@@ -152,7 +162,11 @@ impl Source {
                 let tags = dir.join("tags.json");
                 let _ = dir.create_dir_all_async().await;
 
-                config.download_file(tags, url).await?;
+                if refresh {
+                    config.fetch_file(tags, url).await?;
+                } else {
+                    config.download_file(tags, url).await?;
+                }
             },
             _ => {}
         }
@@ -178,6 +192,7 @@ impl Source {
                             path,
                             url,
                             version: version.clone(),
+                            source: self.clone(),
                         };
 
                         Some((version, file))
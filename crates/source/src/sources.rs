@@ -26,6 +26,10 @@ impl Sources {
         self.sources.insert(source, path);
     }
 
+    pub fn clear(&mut self) {
+        self.sources.clear();
+    }
+
     pub fn len(&self) -> usize {
         self.sources.len()
     }
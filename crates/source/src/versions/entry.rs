@@ -1,3 +1,4 @@
+use crate::Source;
 use mix_version::{Requirement, Version};
 use path::PathBuf;
 use url::Url;
@@ -7,6 +8,9 @@ pub struct Entry {
     pub path: PathBuf,
     pub url: Url,
     pub version: Version,
+
+    /// The source this release was found in.
+    pub source: Source,
 }
 
 impl Entry {
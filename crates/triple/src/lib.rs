@@ -126,6 +126,28 @@ impl Triple {
             (Arch::x86_64, Sys::Linux, Env::Musl) => X86_64_LINUX_MUSL,
         }
     }
+
+    /// returns whether this triple matches a pattern
+    ///
+    /// a pattern is either a single architecture, system or environment (`aarch64`, `musl`),
+    /// or a full triple where `*` matches any component (`*-linux-musl`)
+    #[inline]
+    pub fn matches(&self, pattern: &str) -> bool {
+        let components = [self.arch.as_str(), self.sys.as_str(), self.env.as_str()];
+
+        if !pattern.contains('-') {
+            return components.contains(&pattern);
+        }
+
+        let mut parts = pattern.split('-');
+        let matched = components
+            .iter()
+            .zip(parts.by_ref())
+            .filter(|(component, part)| *part == "*" || component == &part)
+            .count();
+
+        matched == components.len() && parts.next().is_none()
+    }
 }
 
 impl fmt::Display for Triple {
@@ -175,3 +197,45 @@ impl FromStr for Triple {
         Ok(triple)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Triple;
+    use std::str::FromStr;
+
+    fn triple(triple: &str) -> Triple {
+        Triple::from_str(triple).unwrap()
+    }
+
+    #[test]
+    fn matches_architecture() {
+        assert!(triple("x86_64-linux-gnu").matches("x86_64"));
+        assert!(!triple("aarch64-linux-gnu").matches("x86_64"));
+    }
+
+    #[test]
+    fn matches_environment() {
+        assert!(triple("x86_64-linux-musl").matches("musl"));
+        assert!(!triple("x86_64-linux-gnu").matches("musl"));
+    }
+
+    #[test]
+    fn matches_wildcard() {
+        assert!(triple("x86_64-linux-musl").matches("*-linux-musl"));
+        assert!(triple("aarch64-linux-musl").matches("*-linux-musl"));
+        assert!(!triple("x86_64-linux-gnu").matches("*-linux-musl"));
+    }
+
+    #[test]
+    fn matches_full_triple() {
+        assert!(triple("aarch64-linux-gnu").matches("aarch64-linux-gnu"));
+        assert!(!triple("aarch64-linux-musl").matches("aarch64-linux-gnu"));
+        assert!(!triple("x86_64-linux-gnu").matches("aarch64-linux-gnu"));
+    }
+
+    #[test]
+    fn rejects_extra_components() {
+        assert!(!triple("x86_64-linux-gnu").matches("x86_64-pc-linux-gnu"));
+        assert!(!triple("x86_64-linux-gnu").matches("x86_64-linux-gnu-*"));
+    }
+}
@@ -21,10 +21,51 @@ async fn main() -> Result<()> {
         Subcommand::Add(add) => {
             mix_build::build(config.clone(), add.into(), packages.clone()).await?;
         }
+        Subcommand::Doctor(doctor) => {
+            mix_doctor::doctor(config.clone(), doctor.into(), packages.clone()).await?;
+        }
+        Subcommand::Env(env) => {
+            mix_env::env(config.clone(), env.into(), packages.clone()).await?;
+        }
+        Subcommand::Fetch(fetch) => {
+            mix_build::fetch::fetch(config.clone(), fetch.into(), packages.clone()).await?;
+        }
+        Subcommand::Hold(hold) => {
+            mix_hold::hold(config.clone(), hold.into(), packages.clone()).await?;
+        }
+        Subcommand::Info(info) => {
+            mix_info::info(config.clone(), info.into(), packages.clone()).await?;
+        }
+        Subcommand::List(list) => {
+            mix_list::list(config.clone(), list.into(), packages.clone()).await?;
+        }
+        Subcommand::Outdated(outdated) => {
+            mix_outdated::outdated(config.clone(), outdated.into(), packages.clone()).await?;
+        }
+        Subcommand::Remove(remove) => {
+            mix_remove::remove(config.clone(), remove.into(), packages.clone()).await?;
+        }
+        Subcommand::Repo(repo) => {
+            mix_repo::repo(config.clone(), repo.into()).await?;
+        }
+        Subcommand::Search(search) => {
+            mix_search::search(config.clone(), search.into(), packages.clone()).await?;
+        }
         Subcommand::Sync(sync) => {
             mix_sync::sync(config.clone(), sync.into()).await?;
         }
-        _ => todo!(),
+        Subcommand::Tree(tree) => {
+            mix_build::tree::tree(config.clone(), tree.into(), packages.clone()).await?;
+        }
+        Subcommand::Unhold(unhold) => {
+            mix_hold::unhold(config.clone(), unhold.into(), packages.clone()).await?;
+        }
+        Subcommand::Upgrade(upgrade) => {
+            mix_build::upgrade::upgrade(config.clone(), upgrade.into(), packages.clone()).await?;
+        }
+        Subcommand::Why(why) => {
+            mix_build::tree::why(config.clone(), why.into(), packages.clone()).await?;
+        }
     }
 
     config.shell().flush().await?;
@@ -2,8 +2,21 @@ use clap::Parser;
 use path::PathBuf;
 
 mod add;
+mod doctor;
+mod env;
+mod fetch;
+mod hold;
+mod info;
+mod list;
+mod outdated;
 mod remove;
+mod repo;
+mod search;
 mod sync;
+mod tree;
+mod unhold;
+mod upgrade;
+mod why;
 
 #[derive(Parser, Debug)]
 pub enum Subcommand {
@@ -11,13 +24,58 @@ pub enum Subcommand {
     #[clap(alias = "a")]
     Add(add::Options),
 
+    /// check the prefix for problems
+    Doctor(doctor::Options),
+
+    /// enter an environment with package(s)
+    #[clap(alias = "e")]
+    Env(env::Options),
+
+    /// download package sources without building
+    #[clap(alias = "f")]
+    Fetch(fetch::Options),
+
+    /// hold package(s) at a version
+    Hold(hold::Options),
+
+    /// show package information
+    #[clap(alias = "i")]
+    Info(info::Options),
+
+    /// list packages
+    #[clap(alias = "l")]
+    List(list::Options),
+
+    /// report outdated packages
+    Outdated(outdated::Options),
+
     /// remove package(s)
     #[clap(alias = "r")]
     Remove(remove::Options),
 
+    /// manage repositories
+    Repo(repo::Options),
+
+    /// search packages
+    Search(search::Options),
+
     /// sync repos
     #[clap(alias = "s")]
     Sync(sync::Options),
+
+    /// show the dependency tree of a package
+    #[clap(alias = "t")]
+    Tree(tree::Options),
+
+    /// release held package(s)
+    Unhold(unhold::Options),
+
+    /// upgrade installed package(s)
+    #[clap(alias = "u")]
+    Upgrade(upgrade::Options),
+
+    /// show why a package is installed
+    Why(why::Options),
 }
 
 /// milk package mangler
@@ -40,17 +40,27 @@ pub struct Options {
     #[clap(default_value = "1", long, short)]
     pub jobs: usize,
 
-    /// Maps to `--enable/--disable`.
+    /// Set a build option of the package, unless it maps to `--with`, or pass
+    /// `--enable/--disable` where it declares none.
     #[clap(long, multiple_occurrences = true, parse(try_from_str = parse_key_val), short = 'D')]
     pub define: Vec<(String, Value)>,
 
-    /// Maps to `--with/--without`.
+    /// Set a build option of the package which maps to `--with`, or pass `--with/--without`
+    /// where it declares none.
     #[clap(long, multiple_occurrences = true, parse(try_from_str = parse_key_val), short = 'I')]
     pub include: Vec<(String, Value)>,
 
     /// Whether this package requires a seperate build directory.
     #[clap(long)]
     pub build_dir: bool,
+
+    /// Write the resolved install set to this lockfile.
+    #[clap(conflicts_with = "locked", long, parse(from_os_str))]
+    pub lock: Option<PathBuf>,
+
+    /// Install exactly the set in this lockfile, failing if anything drifts.
+    #[clap(long, parse(from_os_str))]
+    pub locked: Option<PathBuf>,
 }
 
 impl Into<Config> for Options {
@@ -64,6 +74,8 @@ impl Into<Config> for Options {
             define: self.define,
             include: self.include,
             build_dir: self.build_dir,
+            lock: self.lock,
+            locked: self.locked,
         }
     }
 }
@@ -0,0 +1,30 @@
+use clap::Parser;
+use mix_doctor::Config;
+use mix_triple::Triple;
+use path::PathBuf;
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// Prefix directory.
+    #[clap(default_value = "/milk", long, parse(from_os_str))]
+    pub prefix: PathBuf,
+
+    /// Target triple.
+    #[clap(default_value = Triple::host().as_str(), long)]
+    pub target: Triple,
+
+    /// Remove partial downloads and stale build directories.
+    #[clap(long)]
+    pub fix: bool,
+}
+
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
+        Config {
+            prefix: self.prefix,
+            target: self.target,
+            fix: self.fix,
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use clap::Parser;
-use mix_atom::Atom;
-use mix_env::Config;
+use mix_atom::Requirement;
+use mix_env::{Config, Kind};
 use mix_triple::Triple;
 use path::PathBuf;
 
@@ -14,16 +14,23 @@ pub struct Options {
     #[clap(default_value = Triple::host().as_str(), long)]
     pub target: Triple,
 
-    /// Package to inspect.
-    pub atom: Atom,
+    /// Print `export` lines for this shell (bash, zsh or fish) instead of spawning `$SHELL`.
+    #[clap(long)]
+    pub print: Option<Kind>,
+
+    /// Packages to include.
+    #[clap(required = true)]
+    pub requirements: Vec<Requirement>,
 }
 
-impl Options {
-    pub fn into_config(self) -> Config {
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
         Config {
             prefix: self.prefix,
             target: self.target,
-            atom: self.atom,
+            requirements: self.requirements,
+            print: self.print,
         }
     }
 }
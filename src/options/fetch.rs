@@ -0,0 +1,36 @@
+use clap::Parser;
+use mix_atom::Requirement;
+use mix_build::fetch::Config;
+use mix_triple::Triple;
+use path::PathBuf;
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// Prefix directory.
+    #[clap(default_value = "/milk", long, parse(from_os_str))]
+    pub prefix: PathBuf,
+
+    /// Target triple.
+    #[clap(default_value = Triple::host().as_str(), long)]
+    pub target: Triple,
+
+    /// Fetch sources for every known package.
+    #[clap(conflicts_with = "requirements", long)]
+    pub all: bool,
+
+    /// Packages to fetch sources for, including their dependencies.
+    #[clap(required_unless_present = "all")]
+    pub requirements: Vec<Requirement>,
+}
+
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
+        Config {
+            prefix: self.prefix,
+            target: self.target,
+            requirements: self.requirements,
+            all: self.all,
+        }
+    }
+}
@@ -1,4 +1,6 @@
 use clap::Parser;
+use mix_list::Config;
+use mix_triple::Triple;
 use path::PathBuf;
 
 #[derive(Parser, Debug)]
@@ -7,11 +9,37 @@ pub struct Options {
     #[clap(default_value = "/milk", long, parse(from_os_str))]
     pub prefix: PathBuf,
 
+    /// Target triple.
+    #[clap(default_value = Triple::host().as_str(), long)]
+    pub target: Triple,
+
     /// List installed only.
     #[clap(long, short)]
     pub installed: bool,
 
+    /// List packages that are not installed only.
+    #[clap(conflicts_with = "installed", long, short)]
+    pub available: bool,
+
     /// Show dependencies.
     #[clap(long, short)]
     pub depend: bool,
+
+    /// Show licenses.
+    #[clap(long, short)]
+    pub license: bool,
+}
+
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
+        Config {
+            prefix: self.prefix,
+            target: self.target,
+            installed: self.installed,
+            available: self.available,
+            depend: self.depend,
+            license: self.license,
+        }
+    }
 }
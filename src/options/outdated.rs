@@ -0,0 +1,29 @@
+use clap::Parser;
+use mix_outdated::Config;
+use path::PathBuf;
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// Prefix directory.
+    #[clap(default_value = "/milk", long, parse(from_os_str))]
+    pub prefix: PathBuf,
+
+    /// Use cached upstream tags only.
+    #[clap(long)]
+    pub offline: bool,
+
+    /// Output as JSON.
+    #[clap(long)]
+    pub json: bool,
+}
+
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
+        Config {
+            prefix: self.prefix,
+            offline: self.offline,
+            json: self.json,
+        }
+    }
+}
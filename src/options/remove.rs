@@ -1,27 +1,8 @@
 use clap::Parser;
 use mix_atom::Requirement;
-use mix_build::{Config, Value};
+use mix_remove::Config;
 use mix_triple::Triple;
 use path::PathBuf;
-use std::str::FromStr;
-
-fn parse_key_val<'s, T>(s: &'s str) -> crate::Result<(T, Value)>
-where
-    T: From<&'s str>,
-{
-    let (k, v) = s
-        .split_once('=')
-        .ok_or_else(|| format!("invalid KEY=value: no `=` found in `{}`", s))?;
-
-    let k = k.into();
-    let v = bool::from_str(v)
-        .map(Value::Bool)
-        .ok()
-        .or_else(|| Some(Value::String(v.into())))
-        .unwrap();
-
-    Ok((k, v))
-}
 
 #[derive(Parser, Debug)]
 pub struct Options {
@@ -33,6 +14,22 @@ pub struct Options {
     #[clap(default_value = Triple::host().as_str(), long)]
     pub target: Triple,
 
-    /// Package to install.
+    /// Package to remove.
     pub requirement: Requirement,
+
+    /// Also remove installed packages that depend on this package.
+    #[clap(long)]
+    pub cascade: bool,
+}
+
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
+        Config {
+            prefix: self.prefix,
+            target: self.target,
+            requirement: self.requirement,
+            cascade: self.cascade,
+        }
+    }
 }
@@ -0,0 +1,58 @@
+use clap::Parser;
+use mix_repo::{Action, Config};
+use path::PathBuf;
+use url::Url;
+
+#[derive(Parser, Debug)]
+pub enum Subcommand {
+    /// add a repository
+    #[clap(alias = "a")]
+    Add {
+        /// Repository id.
+        id: String,
+
+        /// Git URL to clone from.
+        url: Url,
+
+        /// Sync the repository after adding it.
+        #[clap(long, short)]
+        sync: bool,
+    },
+
+    /// remove a repository
+    #[clap(alias = "r")]
+    Remove {
+        /// Repository id.
+        id: String,
+    },
+
+    /// list repositories
+    #[clap(alias = "l")]
+    List,
+}
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// Prefix directory.
+    #[clap(default_value = "/milk", long, parse(from_os_str))]
+    pub prefix: PathBuf,
+
+    #[clap(subcommand)]
+    pub subcommand: Subcommand,
+}
+
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
+        let action = match self.subcommand {
+            Subcommand::Add { id, url, sync } => Action::Add { id, url, sync },
+            Subcommand::Remove { id } => Action::Remove { id },
+            Subcommand::List => Action::List,
+        };
+
+        Config {
+            prefix: self.prefix,
+            action,
+        }
+    }
+}
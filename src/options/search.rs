@@ -0,0 +1,35 @@
+use clap::Parser;
+use mix_id::RepositoryId;
+use mix_search::Config;
+use path::PathBuf;
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// Prefix directory.
+    #[clap(default_value = "/milk", long, parse(from_os_str))]
+    pub prefix: PathBuf,
+
+    /// Only search within this repository.
+    #[clap(long)]
+    pub repo: Option<RepositoryId>,
+
+    /// Output as JSON.
+    #[clap(long)]
+    pub json: bool,
+
+    /// Regular expression to match against package ids, repository ids, sources, descriptions
+    /// and keywords.
+    pub pattern: String,
+}
+
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
+        Config {
+            prefix: self.prefix,
+            pattern: self.pattern,
+            repository: self.repo,
+            json: self.json,
+        }
+    }
+}
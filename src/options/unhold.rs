@@ -0,0 +1,25 @@
+use clap::Parser;
+use mix_atom::Requirement;
+use mix_hold::Config;
+use path::PathBuf;
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// Prefix directory.
+    #[clap(default_value = "/milk", long, parse(from_os_str))]
+    pub prefix: PathBuf,
+
+    /// Packages to release.
+    #[clap(required = true)]
+    pub requirements: Vec<Requirement>,
+}
+
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
+        Config {
+            prefix: self.prefix,
+            requirements: self.requirements,
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use clap::Parser;
+use mix_atom::Requirement;
+use mix_build::upgrade::Config;
+use mix_triple::Triple;
+use path::PathBuf;
+
+#[derive(Parser, Debug)]
+pub struct Options {
+    /// Prefix directory.
+    #[clap(default_value = "/milk", long, parse(from_os_str))]
+    pub prefix: PathBuf,
+
+    /// Target triple.
+    #[clap(default_value = Triple::host().as_str(), long)]
+    pub target: Triple,
+
+    /// Packages to upgrade, defaults to everything installed.
+    pub requirements: Vec<Requirement>,
+
+    /// Jobs to build with.
+    #[clap(default_value = "1", long, short)]
+    pub jobs: usize,
+
+    /// Remove versions superseded by an upgrade.
+    #[clap(long)]
+    pub remove_old: bool,
+
+    /// Only print what would be upgraded.
+    #[clap(long, short = 'n')]
+    pub dry_run: bool,
+}
+
+impl Into<Config> for Options {
+    #[inline]
+    fn into(self) -> Config {
+        Config {
+            prefix: self.prefix,
+            target: self.target,
+            requirements: self.requirements,
+            jobs: self.jobs,
+            remove_old: self.remove_old,
+            dry_run: self.dry_run,
+        }
+    }
+}